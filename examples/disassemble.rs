@@ -0,0 +1,23 @@
+//! Prints an sBPF disassembly listing of a program binary
+//!
+//! Usage: cargo run --example disassemble -- path/to/program.so
+
+use soon_guard::analysis::{elf::Elf, sbpf};
+
+fn main() {
+    let path = std::env::args()
+        .nth(1)
+        .expect("usage: disassemble <program.so>");
+    let bytes = std::fs::read(&path).expect("failed to read program binary");
+
+    let elf = Elf::parse(&bytes).expect("not a valid SBF program");
+    let text = elf.text().expect("program has no .text section");
+
+    println!(
+        "{}: {:?}, {} instructions",
+        path,
+        elf.sbpf_version(),
+        text.data.len() / sbpf::INSN_SIZE
+    );
+    print!("{}", sbpf::disassemble(text.data, elf.sbpf_version()));
+}
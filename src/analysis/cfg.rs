@@ -0,0 +1,487 @@
+// program/src/analysis/cfg.rs
+//! Control-flow graph of a program's `.text`
+//!
+//! Blocks are not materialized: the graph only keeps a bitset of block
+//! leaders and the sorted function entries, and decodes a block's instructions
+//! when it is visited. This keeps analysis of large programs within the small
+//! on-chain heap.
+
+use super::{
+    elf::{Elf, ElfError, R_BPF_64_32, R_BPF_64_RELATIVE},
+    sbpf::{DecodedInsn, Decoder, Insn, JumpCondition, SbpfVersion, INSN_SIZE},
+    syscalls,
+};
+
+/// Syscalls that never return control to the program
+pub const NORETURN_SYSCALLS: &[&str] = &["abort", "sol_panic_"];
+
+/// A `call` resolved through a relocation to a symbol outside `.text`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ExternalCall<'a> {
+    pub pc: usize,
+    pub name: &'a str,
+}
+
+/// How control leaves a basic block
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BlockExit {
+    /// Runs into the block starting at the given pc
+    Fallthrough(usize),
+    /// `ja`
+    Jump(usize),
+    /// Conditional jump
+    Branch { taken: usize, fallthrough: usize },
+    /// `exit`
+    Return,
+    /// Call to a syscall that never returns
+    Abort,
+    /// Invalid instruction, or execution running off the end of `.text`
+    Trap,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    /// Pc of the first instruction
+    pub start: usize,
+    /// Pc of the last instruction
+    pub last: usize,
+    /// Pc following the block
+    pub end: usize,
+    pub exit: BlockExit,
+}
+
+/// Where the `call` at a given pc transfers control
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CallTarget<'a> {
+    /// Function inside `.text`
+    Internal(usize),
+    /// Call to a named symbol outside `.text`, usually a syscall
+    External(&'a str),
+    /// Unknown syscall identified by the hash of its name
+    Hashed(u32),
+}
+
+#[derive(Debug, Clone)]
+pub struct Cfg<'a> {
+    text: &'a [u8],
+    version: SbpfVersion,
+    entrypoint: usize,
+    leaders: BitSet,
+    /// Sorted entry pcs of all known functions
+    functions: Vec<usize>,
+    /// Sorted by pc
+    external_calls: Vec<ExternalCall<'a>>,
+}
+
+/// Which blocks can execute, and which functions can never finish
+#[derive(Debug, Clone)]
+pub struct Reachability {
+    reachable: BitSet,
+    /// Entries of functions from which no path returns, aborts or traps
+    pub non_terminating_functions: Vec<usize>,
+}
+
+impl BasicBlock {
+    /// Pcs of the blocks control can continue in
+    pub fn successors(&self) -> impl Iterator<Item = usize> {
+        let (first, second) = match self.exit {
+            BlockExit::Fallthrough(next) | BlockExit::Jump(next) => (Some(next), None),
+            BlockExit::Branch { taken, fallthrough } => (Some(taken), Some(fallthrough)),
+            BlockExit::Return | BlockExit::Abort | BlockExit::Trap => (None, None),
+        };
+        first.into_iter().chain(second)
+    }
+
+    /// Returns true for blocks that end in `ja` back to their own start
+    pub fn is_self_loop(&self) -> bool {
+        self.exit == BlockExit::Jump(self.start)
+    }
+}
+
+impl<'a> Cfg<'a> {
+    /// Builds the graph of `text`
+    ///
+    /// Functions are discovered from the entrypoint, `entries` and internal
+    /// call targets. `external_calls` must be sorted by pc.
+    pub fn new(
+        text: &'a [u8],
+        version: SbpfVersion,
+        entrypoint: usize,
+        entries: impl IntoIterator<Item = usize>,
+        external_calls: Vec<ExternalCall<'a>>,
+    ) -> Self {
+        let slot_count = text.len() / INSN_SIZE;
+        let mut cfg = Self {
+            text,
+            version,
+            entrypoint,
+            leaders: BitSet::new(slot_count),
+            functions: entries.into_iter().filter(|&pc| pc < slot_count).collect(),
+            external_calls,
+        };
+        if entrypoint < slot_count {
+            cfg.functions.push(entrypoint);
+        }
+
+        for insn in cfg.decoder() {
+            match insn.insn {
+                Insn::Jump { .. } => {
+                    cfg.mark_leader(insn.jump_target());
+                    cfg.mark_leader(Some(insn.next_pc()));
+                }
+                Insn::Exit | Insn::Invalid { .. } => cfg.mark_leader(Some(insn.next_pc())),
+                Insn::Call { .. } => match cfg.call_target(&insn) {
+                    Some(CallTarget::Internal(target)) if target < slot_count => {
+                        cfg.functions.push(target)
+                    }
+                    Some(CallTarget::External(name)) if NORETURN_SYSCALLS.contains(&name) => {
+                        cfg.mark_leader(Some(insn.next_pc()))
+                    }
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+
+        cfg.functions.sort_unstable();
+        cfg.functions.dedup();
+        for index in 0..cfg.functions.len() {
+            cfg.leaders.insert(cfg.functions[index]);
+        }
+        cfg
+    }
+
+    /// Builds the graph of the ELF's `.text`, taking function entries from the
+    /// entrypoint, function symbols and relocated code addresses
+    pub fn from_elf(elf: &Elf<'a>) -> Result<Self, ElfError> {
+        let text = elf.text().ok_or(ElfError::InvalidSectionTable)?;
+        let version = elf.sbpf_version();
+        let decoder = Decoder::new(text.data, version);
+        let text_pc = |address: u64| -> Option<usize> {
+            if !text.contains_address(address) {
+                return None;
+            }
+            Some(((address - text.header.sh_addr) as usize) / INSN_SIZE)
+        };
+
+        let entrypoint = text_pc(elf.header().e_entry).unwrap_or(0);
+        let mut entries = Vec::new();
+        let dynamic_symbols = elf.dynamic_symbols()?;
+        for symbol in dynamic_symbols.iter().chain(elf.symbols()?.iter()) {
+            if symbol.is_function() {
+                entries.extend(text_pc(symbol.value));
+            }
+        }
+
+        let mut external_calls = Vec::new();
+        for relocation in elf.relocations() {
+            match relocation.relocation_type {
+                R_BPF_64_32 => {
+                    let symbol = dynamic_symbols.get(relocation.symbol_index as usize);
+                    if let (Some(pc), Some(symbol)) = (text_pc(relocation.offset), symbol) {
+                        // Relocated calls to functions inside `.text` stay internal
+                        if !symbol.is_function() || text_pc(symbol.value).is_none() {
+                            external_calls.push(ExternalCall { pc, name: symbol.name });
+                        }
+                    }
+                }
+                // Addresses of code taken by `lddw` or stored in data, e.g. vtables
+                R_BPF_64_RELATIVE => {
+                    let address = match text_pc(relocation.offset) {
+                        Some(pc) => match decoder.decode_at(pc).map(|insn| insn.insn) {
+                            Some(Insn::LoadImm64 { imm, .. }) => Some(imm),
+                            _ => None,
+                        },
+                        // SBFv1 keeps the address in the upper half of the data word
+                        None => elf.section_at_address(relocation.offset).and_then(|section| {
+                            let start = (relocation.offset - section.header.sh_addr) as usize + 4;
+                            let bytes = section.data.get(start..start + 4)?;
+                            Some(u32::from_le_bytes(bytes.try_into().ok()?) as u64)
+                        }),
+                    };
+                    entries.extend(address.and_then(text_pc));
+                }
+                _ => {}
+            }
+        }
+        external_calls.sort_unstable_by_key(|call| call.pc);
+
+        Ok(Self::new(text.data, version, entrypoint, entries, external_calls))
+    }
+
+    pub fn decoder(&self) -> Decoder<'a> {
+        Decoder::new(self.text, self.version)
+    }
+
+    /// Pc the runtime starts execution at
+    pub fn entrypoint(&self) -> usize {
+        self.entrypoint
+    }
+
+    pub fn slot_count(&self) -> usize {
+        self.text.len() / INSN_SIZE
+    }
+
+    /// Sorted entry pcs of all known functions
+    pub fn functions(&self) -> &[usize] {
+        &self.functions
+    }
+
+    /// Entry of the function the instruction at `pc` belongs to
+    pub fn function_containing(&self, pc: usize) -> Option<usize> {
+        match self.functions.binary_search(&pc) {
+            Ok(index) => Some(self.functions[index]),
+            Err(0) => None,
+            Err(index) => Some(self.functions[index - 1]),
+        }
+    }
+
+    pub fn is_function_entry(&self, pc: usize) -> bool {
+        self.functions.binary_search(&pc).is_ok()
+    }
+
+    pub fn external_call(&self, pc: usize) -> Option<&ExternalCall<'a>> {
+        let index = self.external_calls.binary_search_by_key(&pc, |call| call.pc).ok()?;
+        self.external_calls.get(index)
+    }
+
+    pub fn call_target(&self, insn: &DecodedInsn) -> Option<CallTarget<'a>> {
+        let Insn::Call { src, imm } = insn.insn else {
+            return None;
+        };
+        if let Some(call) = self.external_call(insn.pc) {
+            return Some(CallTarget::External(call.name));
+        }
+        if src == 0 {
+            let hash = imm as u32;
+            return Some(syscalls::resolve(hash).map_or(CallTarget::Hashed(hash), CallTarget::External));
+        }
+        let target = insn.pc as i64 + 1 + imm as i64;
+        usize::try_from(target).ok().map(CallTarget::Internal)
+    }
+
+    /// The basic block starting at `start`, which must be a leader
+    pub fn block_at(&self, start: usize) -> Option<BasicBlock> {
+        let decoder = self.decoder();
+        let mut insn = decoder.decode_at(start)?;
+        loop {
+            let next = insn.next_pc();
+            let exit = match insn.insn {
+                Insn::Jump { cond: JumpCondition::Always, .. } => {
+                    Some(BlockExit::Jump(insn.jump_target().unwrap_or(usize::MAX)))
+                }
+                Insn::Jump { .. } => Some(BlockExit::Branch {
+                    taken: insn.jump_target().unwrap_or(usize::MAX),
+                    fallthrough: next,
+                }),
+                Insn::Exit => Some(BlockExit::Return),
+                Insn::Invalid { .. } => Some(BlockExit::Trap),
+                Insn::Call { .. } if self.is_noreturn_call(&insn) => Some(BlockExit::Abort),
+                _ if next >= self.slot_count() => Some(BlockExit::Trap),
+                _ if self.leaders.contains(next) => Some(BlockExit::Fallthrough(next)),
+                _ => None,
+            };
+            if let Some(exit) = exit {
+                return Some(BasicBlock {
+                    start,
+                    last: insn.pc,
+                    end: next,
+                    exit,
+                });
+            }
+            insn = decoder.decode_at(next)?;
+        }
+    }
+
+    /// All blocks in address order
+    pub fn blocks(&self) -> impl Iterator<Item = BasicBlock> + '_ {
+        let mut start = Some(0);
+        std::iter::from_fn(move || {
+            let block = self.block_at(start?)?;
+            start = Some(block.end);
+            Some(block)
+        })
+    }
+
+    /// Walks every function from its entry without following calls; called
+    /// functions are walked from their own entries
+    pub fn reachability(&self) -> Reachability {
+        let slot_count = self.slot_count();
+        let mut reachable = BitSet::new(slot_count);
+        let mut visited = BitSet::new(slot_count);
+        let mut non_terminating_functions = Vec::new();
+        let mut stack = Vec::new();
+        let mut walked = Vec::new();
+
+        for &entry in &self.functions {
+            let mut terminates = false;
+            stack.push(entry);
+            visited.insert(entry);
+            while let Some(start) = stack.pop() {
+                walked.push(start);
+                reachable.insert(start);
+                let Some(block) = self.block_at(start) else {
+                    terminates = true;
+                    continue;
+                };
+                if matches!(block.exit, BlockExit::Return | BlockExit::Abort | BlockExit::Trap) {
+                    terminates = true;
+                }
+                for successor in block.successors() {
+                    // Running into another function counts as leaving this one
+                    if successor >= slot_count
+                        || (successor != entry && self.is_function_entry(successor))
+                    {
+                        terminates = true;
+                    } else if !visited.contains(successor) {
+                        visited.insert(successor);
+                        stack.push(successor);
+                    }
+                }
+            }
+            if !terminates {
+                non_terminating_functions.push(entry);
+            }
+            for start in walked.drain(..) {
+                visited.remove(start);
+            }
+        }
+
+        Reachability {
+            reachable,
+            non_terminating_functions,
+        }
+    }
+
+    fn is_noreturn_call(&self, insn: &DecodedInsn) -> bool {
+        matches!(
+            self.call_target(insn),
+            Some(CallTarget::External(name)) if NORETURN_SYSCALLS.contains(&name)
+        )
+    }
+
+    fn mark_leader(&mut self, pc: Option<usize>) {
+        if let Some(pc) = pc.filter(|&pc| pc < self.leaders.len()) {
+            self.leaders.insert(pc);
+        }
+    }
+}
+
+impl Reachability {
+    /// Returns true if the block starting at `start` can execute
+    pub fn is_reachable(&self, start: usize) -> bool {
+        self.reachable.contains(start)
+    }
+}
+
+/// Fixed-size set of pcs
+#[derive(Debug, Clone)]
+struct BitSet {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl BitSet {
+    fn new(len: usize) -> Self {
+        Self {
+            words: vec![0; len.div_ceil(64)],
+            len,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn insert(&mut self, index: usize) {
+        self.words[index / 64] |= 1 << (index % 64);
+    }
+
+    fn remove(&mut self, index: usize) {
+        self.words[index / 64] &= !(1 << (index % 64));
+    }
+
+    fn contains(&self, index: usize) -> bool {
+        index < self.len && self.words[index / 64] & (1 << (index % 64)) != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{elf::test_utils::ElfBuilder, sbpf::test_utils::slot};
+    use super::*;
+
+    #[test]
+    fn test_basic_blocks() {
+        let text = [
+            slot(0xb7, 0, 0, 0, 0),  // 0: mov64 r0, 0
+            slot(0x15, 1, 0, 2, 0),  // 1: jeq r1, 0, +2
+            slot(0x85, 0, 1, 0, 2),  // 2: call +2
+            slot(0x05, 0, 0, -2, 0), // 3: ja -2
+            slot(0x95, 0, 0, 0, 0),  // 4: exit
+            slot(0xb7, 0, 0, 0, 1),  // 5: mov64 r0, 1
+            slot(0x95, 0, 0, 0, 0),  // 6: exit
+        ]
+        .concat();
+        let cfg = Cfg::new(&text, SbpfVersion::V1, 0, [], Vec::new());
+        assert_eq!(cfg.functions(), &[0, 5]);
+        assert_eq!(cfg.function_containing(3), Some(0));
+        assert_eq!(cfg.function_containing(6), Some(5));
+
+        let blocks: Vec<_> = cfg.blocks().collect();
+        assert_eq!(blocks.len(), 4);
+        assert_eq!(blocks[0].exit, BlockExit::Branch { taken: 4, fallthrough: 2 });
+        assert_eq!(blocks[1], BasicBlock { start: 2, last: 3, end: 4, exit: BlockExit::Jump(2) });
+        assert_eq!(blocks[2].exit, BlockExit::Return);
+        assert_eq!(blocks[3].start, 5);
+        assert!(blocks[1].is_self_loop());
+        assert_eq!(blocks[0].successors().collect::<Vec<_>>(), vec![4, 2]);
+    }
+
+    #[test]
+    fn test_reachability() {
+        let text = [
+            slot(0x95, 0, 0, 0, 0),  // 0: exit
+            slot(0xb7, 0, 0, 0, 0),  // 1: mov64 r0, 0 (dead)
+            slot(0x95, 0, 0, 0, 0),  // 2: exit (dead)
+            slot(0x07, 1, 0, 0, 1),  // 3: add64 r1, 1
+            slot(0x05, 0, 0, -2, 0), // 4: ja -2
+        ]
+        .concat();
+        let cfg = Cfg::new(&text, SbpfVersion::V1, 0, [3], Vec::new());
+        let reachability = cfg.reachability();
+        assert!(reachability.is_reachable(0));
+        assert!(!reachability.is_reachable(1));
+        assert!(reachability.is_reachable(3));
+        assert_eq!(reachability.non_terminating_functions, vec![3]);
+    }
+
+    #[test]
+    fn test_from_elf() {
+        let text = [
+            slot(0x85, 0, 1, 0, -1), // 0: call abort
+            slot(0xb7, 0, 0, 0, 0),  // 1: mov64 r0, 0 (after abort, dead)
+            slot(0x95, 0, 0, 0, 0),  // 2: exit
+            slot(0x85, 0, 1, 0, -1), // 3: call sol_log_
+            slot(0x95, 0, 0, 0, 0),  // 4: exit
+        ]
+        .concat();
+        let bytes = ElfBuilder::new(&text)
+            .function("helper", 3)
+            .syscall(0, "abort")
+            .syscall(3, "sol_log_")
+            .build();
+        let elf = Elf::parse(&bytes).unwrap();
+        let cfg = Cfg::from_elf(&elf).unwrap();
+
+        assert_eq!(cfg.functions(), &[0, 3]);
+        assert_eq!(cfg.block_at(0).unwrap().exit, BlockExit::Abort);
+        assert_eq!(cfg.block_at(3).unwrap().exit, BlockExit::Return);
+        let call = cfg.decoder().decode_at(3).unwrap();
+        assert_eq!(cfg.call_target(&call), Some(CallTarget::External("sol_log_")));
+
+        let reachability = cfg.reachability();
+        assert!(!reachability.is_reachable(1));
+        assert!(reachability.non_terminating_functions.is_empty());
+    }
+}
@@ -0,0 +1,285 @@
+// program/src/analysis/compute.rs
+//! Static estimate of the compute units a program consumes
+//!
+//! Every executed instruction costs one unit and every syscall its base cost.
+//! A function's estimate is the most expensive acyclic path from its entry,
+//! with the estimates of called functions added at their call sites. Loops,
+//! recursion and indirect calls cannot be bounded statically: their functions
+//! are flagged, and their estimate counts each loop body once.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::{
+    cfg::{BasicBlock, CallTarget, Cfg},
+    sbpf::{DecodedInsn, Insn},
+};
+
+/// Units charged by the runtime for a syscall before any size-dependent cost
+pub fn syscall_base_cost(name: &str) -> u64 {
+    match name {
+        "sol_memcpy_" | "sol_memmove_" | "sol_memcmp_" | "sol_memset_" => 10,
+        "sol_sha256" | "sol_keccak256" | "sol_blake3" => 85,
+        "sol_invoke_signed_c" | "sol_invoke_signed_rust" => 1_000,
+        "sol_create_program_address" | "sol_try_find_program_address" => 1_500,
+        "sol_curve_validate_point" => 169,
+        "sol_curve_group_op" => 2_208,
+        "sol_curve_multiscalar_mul" => 2_303,
+        "sol_alt_bn128_group_op" => 3_840,
+        "sol_alt_bn128_compression" => 13_610,
+        "sol_poseidon" => 9_326,
+        "sol_secp256k1_recover" => 25_000,
+        _ => 100,
+    }
+}
+
+/// Estimated cost of one function
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FunctionCost {
+    pub entry: usize,
+    /// Units consumed on the most expensive acyclic path, including callees
+    pub worst_case: u64,
+    /// Some path loops, recurses or calls through a register, so the real
+    /// cost can exceed `worst_case`
+    pub unbounded: bool,
+}
+
+/// Estimates every function of `cfg`, in entry order
+pub fn estimate(cfg: &Cfg) -> Vec<FunctionCost> {
+    let functions = cfg.functions();
+    let mut costs: Vec<Option<FunctionCost>> = vec![None; functions.len()];
+
+    for root in 0..functions.len() {
+        if costs[root].is_some() {
+            continue;
+        }
+        // (function index, callees not yet visited); callees are estimated
+        // before their callers, and calling a function on the stack recurses
+        let mut stack = vec![(root, callees(cfg, functions[root]))];
+        while let Some((index, pending)) = stack.last_mut() {
+            let index = *index;
+            match pending.pop() {
+                Some(target) => {
+                    let Ok(callee) = functions.binary_search(&target) else {
+                        continue;
+                    };
+                    let recursive = stack.iter().any(|(function, _)| *function == callee);
+                    if costs[callee].is_none() && !recursive {
+                        stack.push((callee, callees(cfg, functions[callee])));
+                    }
+                }
+                None => {
+                    costs[index] = Some(function_cost(cfg, functions[index], &costs, functions));
+                    stack.pop();
+                }
+            }
+        }
+    }
+    costs.into_iter().flatten().collect()
+}
+
+/// Entries of the functions `entry` calls directly
+fn callees(cfg: &Cfg, entry: usize) -> Vec<usize> {
+    let mut callees = Vec::new();
+    walk_function(cfg, entry, |block| {
+        for decoded in instructions(cfg, block) {
+            if let Some(CallTarget::Internal(target)) = cfg.call_target(&decoded) {
+                if !callees.contains(&target) {
+                    callees.push(target);
+                }
+            }
+        }
+    });
+    callees
+}
+
+/// Longest path through the blocks of the function at `entry`
+///
+/// Blocks are costed in depth-first postorder, when all successors other than
+/// loop headers are already costed.
+fn function_cost(
+    cfg: &Cfg,
+    entry: usize,
+    costs: &[Option<FunctionCost>],
+    functions: &[usize],
+) -> FunctionCost {
+    // Longest path from each finished block
+    let mut finished: BTreeMap<usize, u64> = BTreeMap::new();
+    let mut on_stack: BTreeSet<usize> = BTreeSet::new();
+    let mut unbounded = false;
+
+    // (block, its cost, successors not yet visited)
+    let mut stack: Vec<(BasicBlock, u64, Vec<usize>)> = Vec::new();
+    let enter = |start: usize, stack: &mut Vec<_>, on_stack: &mut BTreeSet<usize>, unbounded: &mut bool| {
+        let Some(block) = cfg.block_at(start) else {
+            return;
+        };
+        let (cost, block_unbounded) = block_cost(cfg, &block, costs, functions);
+        *unbounded |= block_unbounded;
+        let successors = block
+            .successors()
+            // Running into another function leaves this one
+            .filter(|&successor| {
+                successor < cfg.slot_count() && (successor == entry || !cfg.is_function_entry(successor))
+            })
+            .collect();
+        on_stack.insert(start);
+        stack.push((block, cost, successors));
+    };
+    enter(entry, &mut stack, &mut on_stack, &mut unbounded);
+
+    while let Some((block, cost, successors)) = stack.last_mut() {
+        match successors.pop() {
+            // A back edge
+            Some(successor) if on_stack.contains(&successor) => unbounded = true,
+            Some(successor) if finished.contains_key(&successor) => {}
+            Some(successor) => enter(successor, &mut stack, &mut on_stack, &mut unbounded),
+            None => {
+                let block = *block;
+                let cost = *cost;
+                let longest_successor = block
+                    .successors()
+                    .filter_map(|successor| finished.get(&successor))
+                    .max()
+                    .copied()
+                    .unwrap_or(0);
+                finished.insert(block.start, cost.saturating_add(longest_successor));
+                on_stack.remove(&block.start);
+                stack.pop();
+            }
+        }
+    }
+
+    FunctionCost {
+        entry,
+        worst_case: finished.get(&entry).copied().unwrap_or(0),
+        unbounded,
+    }
+}
+
+/// Cost of executing `block` once, and whether it makes the function unbounded
+fn block_cost(cfg: &Cfg, block: &BasicBlock, costs: &[Option<FunctionCost>], functions: &[usize]) -> (u64, bool) {
+    let mut cost = 0u64;
+    let mut unbounded = false;
+    for decoded in instructions(cfg, block) {
+        cost += 1;
+        match decoded.insn {
+            Insn::Call { .. } => match cfg.call_target(&decoded) {
+                Some(CallTarget::Internal(target)) => {
+                    let callee = functions
+                        .binary_search(&target)
+                        .ok()
+                        .and_then(|index| costs[index]);
+                    match callee {
+                        Some(callee) => {
+                            cost = cost.saturating_add(callee.worst_case);
+                            unbounded |= callee.unbounded;
+                        }
+                        // Still being estimated: a recursive call
+                        None => unbounded = true,
+                    }
+                }
+                Some(CallTarget::External(name)) => cost += syscall_base_cost(name),
+                Some(CallTarget::Hashed(_)) | None => cost += syscall_base_cost(""),
+            },
+            Insn::CallReg { .. } => unbounded = true,
+            _ => {}
+        }
+    }
+    (cost, unbounded)
+}
+
+/// Decoded instructions of `block`
+fn instructions<'a>(cfg: &Cfg<'a>, block: &BasicBlock) -> impl Iterator<Item = DecodedInsn> + 'a {
+    let decoder = cfg.decoder();
+    let end = block.end;
+    std::iter::successors(decoder.decode_at(block.start), move |insn| decoder.decode_at(insn.next_pc()))
+        .take_while(move |insn| insn.pc < end)
+}
+
+/// Calls `visit` on every block of the function at `entry`
+fn walk_function(cfg: &Cfg, entry: usize, mut visit: impl FnMut(&BasicBlock)) {
+    let mut visited = BTreeSet::from([entry]);
+    let mut stack = vec![entry];
+    while let Some(start) = stack.pop() {
+        let Some(block) = cfg.block_at(start) else {
+            continue;
+        };
+        visit(&block);
+        for successor in block.successors() {
+            if successor >= cfg.slot_count()
+                || (successor != entry && cfg.is_function_entry(successor))
+                || !visited.insert(successor)
+            {
+                continue;
+            }
+            stack.push(successor);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        cfg::ExternalCall,
+        sbpf::{test_utils::slot, SbpfVersion},
+    };
+    use super::*;
+
+    #[test]
+    fn test_longest_path() {
+        let text = [
+            slot(0x15, 1, 0, 2, 0),  // 0: jeq r1, 0, +2
+            slot(0xb7, 0, 0, 0, 1),  // 1: mov64 r0, 1
+            slot(0xb7, 0, 0, 0, 2),  // 2: mov64 r0, 2
+            slot(0x85, 0, 1, 0, -1), // 3: call sol_log_
+            slot(0x95, 0, 0, 0, 0),  // 4: exit
+        ]
+        .concat();
+        let external_calls = vec![ExternalCall { pc: 3, name: "sol_log_" }];
+        let cfg = Cfg::new(&text, SbpfVersion::V1, 0, [], external_calls);
+        let costs = estimate(&cfg);
+        assert_eq!(
+            costs,
+            vec![FunctionCost {
+                entry: 0,
+                worst_case: 5 + 100,
+                unbounded: false,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_callees_and_loops() {
+        let text = [
+            slot(0x85, 0, 1, 0, 2),   // 0: call +2
+            slot(0x85, 0, 1, 0, 3),   // 1: call +3
+            slot(0x95, 0, 0, 0, 0),   // 2: exit
+            slot(0xb7, 0, 0, 0, 0),   // 3: mov64 r0, 0
+            slot(0x95, 0, 0, 0, 0),   // 4: exit
+            slot(0x07, 1, 0, 0, -1),  // 5: add64 r1, -1
+            slot(0x55, 1, 0, -2, 0),  // 6: jne r1, 0, -2
+            slot(0x95, 0, 0, 0, 0),   // 7: exit
+        ]
+        .concat();
+        let cfg = Cfg::new(&text, SbpfVersion::V1, 0, [], Vec::new());
+        let costs = estimate(&cfg);
+        assert_eq!(costs.len(), 3);
+        assert_eq!(costs[1], FunctionCost { entry: 3, worst_case: 2, unbounded: false });
+        assert_eq!(costs[2], FunctionCost { entry: 5, worst_case: 3, unbounded: true });
+        // Both calls and the exit, plus the callees; the loop taints the caller
+        assert_eq!(costs[0], FunctionCost { entry: 0, worst_case: 3 + 2 + 3, unbounded: true });
+    }
+
+    #[test]
+    fn test_recursion() {
+        let text = [
+            slot(0x15, 1, 0, 1, 0),  // 0: jeq r1, 0, +1
+            slot(0x85, 0, 1, 0, -2), // 1: call -2
+            slot(0x95, 0, 0, 0, 0),  // 2: exit
+        ]
+        .concat();
+        let cfg = Cfg::new(&text, SbpfVersion::V1, 0, [], Vec::new());
+        let costs = estimate(&cfg);
+        assert_eq!(costs, vec![FunctionCost { entry: 0, worst_case: 3, unbounded: true }]);
+    }
+}
@@ -0,0 +1,607 @@
+// program/src/analysis/elf.rs
+//! Zero-copy reader for the ELF64 files produced by the SBF toolchain
+//!
+//! Only the parts the analyzer needs are decoded: the file header, the
+//! section table, symbol tables and `SHT_REL` relocations. All returned
+//! slices borrow from the program bytes, so nothing is copied on-chain.
+
+use thiserror::Error;
+
+use super::sbpf::SbpfVersion;
+
+pub const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+pub const ELFCLASS64: u8 = 2;
+pub const ELFDATA2LSB: u8 = 1;
+
+pub const EM_BPF: u16 = 247;
+pub const EM_SBF: u16 = 263;
+
+/// `e_flags` bit set by the toolchain for SBFv2 binaries
+pub const EF_SBF_V2: u32 = 0x20;
+
+// Section types
+pub const SHT_NULL: u32 = 0;
+pub const SHT_PROGBITS: u32 = 1;
+pub const SHT_SYMTAB: u32 = 2;
+pub const SHT_STRTAB: u32 = 3;
+pub const SHT_DYNAMIC: u32 = 6;
+pub const SHT_NOBITS: u32 = 8;
+pub const SHT_REL: u32 = 9;
+pub const SHT_DYNSYM: u32 = 11;
+
+// Symbol types
+pub const STT_NOTYPE: u8 = 0;
+pub const STT_OBJECT: u8 = 1;
+pub const STT_FUNC: u8 = 2;
+
+// Relocation types
+pub const R_BPF_64_64: u32 = 1;
+pub const R_BPF_64_RELATIVE: u32 = 8;
+pub const R_BPF_64_32: u32 = 10;
+
+const FILE_HEADER_SIZE: usize = 64;
+const SECTION_HEADER_SIZE: usize = 64;
+const SYMBOL_SIZE: usize = 24;
+const RELOCATION_SIZE: usize = 16;
+
+#[derive(Error, Debug, Copy, Clone, PartialEq)]
+pub enum ElfError {
+    #[error("Not an ELF file")]
+    InvalidMagic,
+
+    #[error("Only little-endian ELF64 files are supported")]
+    UnsupportedClass,
+
+    #[error("Unsupported machine type {0}")]
+    UnsupportedMachine(u16),
+
+    #[error("Header or table extends past the end of the file")]
+    OutOfBounds,
+
+    #[error("Malformed section header table")]
+    InvalidSectionTable,
+
+    #[error("Malformed string table entry")]
+    InvalidString,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FileHeader {
+    pub e_type: u16,
+    pub e_machine: u16,
+    pub e_entry: u64,
+    pub e_shoff: u64,
+    pub e_flags: u32,
+    pub e_shnum: u16,
+    pub e_shstrndx: u16,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SectionHeader {
+    pub sh_name: u32,
+    pub sh_type: u32,
+    pub sh_flags: u64,
+    pub sh_addr: u64,
+    pub sh_offset: u64,
+    pub sh_size: u64,
+    pub sh_link: u32,
+    pub sh_info: u32,
+    pub sh_entsize: u64,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Section<'a> {
+    pub index: usize,
+    pub name: &'a str,
+    pub header: SectionHeader,
+    pub data: &'a [u8],
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Symbol<'a> {
+    pub name: &'a str,
+    pub info: u8,
+    pub shndx: u16,
+    pub value: u64,
+    pub size: u64,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Relocation {
+    /// Virtual address of the patched instruction
+    pub offset: u64,
+    pub relocation_type: u32,
+    /// Index into the symbol table linked from the relocation section
+    pub symbol_index: u32,
+}
+
+/// A parsed view over an SBF program binary
+#[derive(Debug, Clone)]
+pub struct Elf<'a> {
+    bytes: &'a [u8],
+    header: FileHeader,
+    section_headers: Vec<SectionHeader>,
+    section_names: &'a [u8],
+}
+
+impl<'a> Section<'a> {
+    /// Returns true if `address` falls inside this section's virtual range
+    pub fn contains_address(&self, address: u64) -> bool {
+        address >= self.header.sh_addr
+            && address - self.header.sh_addr < self.header.sh_size
+    }
+}
+
+impl<'a> Symbol<'a> {
+    pub fn symbol_type(&self) -> u8 {
+        self.info & 0xf
+    }
+
+    pub fn is_function(&self) -> bool {
+        self.symbol_type() == STT_FUNC
+    }
+}
+
+impl<'a> Elf<'a> {
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, ElfError> {
+        if bytes.len() < FILE_HEADER_SIZE {
+            return Err(ElfError::OutOfBounds);
+        }
+        if bytes[..4] != ELF_MAGIC {
+            return Err(ElfError::InvalidMagic);
+        }
+        if bytes[4] != ELFCLASS64 || bytes[5] != ELFDATA2LSB {
+            return Err(ElfError::UnsupportedClass);
+        }
+
+        let header = FileHeader {
+            e_type: read_u16(bytes, 16)?,
+            e_machine: read_u16(bytes, 18)?,
+            e_entry: read_u64(bytes, 24)?,
+            e_shoff: read_u64(bytes, 40)?,
+            e_flags: read_u32(bytes, 48)?,
+            e_shnum: read_u16(bytes, 60)?,
+            e_shstrndx: read_u16(bytes, 62)?,
+        };
+
+        if header.e_machine != EM_BPF && header.e_machine != EM_SBF {
+            return Err(ElfError::UnsupportedMachine(header.e_machine));
+        }
+        if read_u16(bytes, 58)? as usize != SECTION_HEADER_SIZE {
+            return Err(ElfError::InvalidSectionTable);
+        }
+
+        let table_offset = to_usize(header.e_shoff)?;
+        let table = slice(bytes, table_offset, header.e_shnum as usize * SECTION_HEADER_SIZE)?;
+        let section_headers = table
+            .chunks_exact(SECTION_HEADER_SIZE)
+            .map(parse_section_header)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Every section must lie within the file so later lookups cannot fail
+        for section_header in &section_headers {
+            if section_header.sh_type != SHT_NOBITS {
+                slice(
+                    bytes,
+                    to_usize(section_header.sh_offset)?,
+                    to_usize(section_header.sh_size)?,
+                )?;
+            }
+        }
+
+        let names_header = section_headers
+            .get(header.e_shstrndx as usize)
+            .ok_or(ElfError::InvalidSectionTable)?;
+        if names_header.sh_type != SHT_STRTAB {
+            return Err(ElfError::InvalidSectionTable);
+        }
+        let section_names = section_data(bytes, names_header);
+
+        let elf = Self {
+            bytes,
+            header,
+            section_headers,
+            section_names,
+        };
+        for section_header in &elf.section_headers {
+            read_str(elf.section_names, section_header.sh_name)?;
+        }
+
+        Ok(elf)
+    }
+
+    pub fn header(&self) -> &FileHeader {
+        &self.header
+    }
+
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// SBFv2 binaries use a different encoding for some instructions
+    pub fn is_sbpf_v2(&self) -> bool {
+        self.header.e_flags & EF_SBF_V2 != 0
+    }
+
+    pub fn sbpf_version(&self) -> SbpfVersion {
+        if self.is_sbpf_v2() {
+            SbpfVersion::V2
+        } else {
+            SbpfVersion::V1
+        }
+    }
+
+    pub fn section_count(&self) -> usize {
+        self.section_headers.len()
+    }
+
+    pub fn section(&self, index: usize) -> Option<Section<'a>> {
+        let header = *self.section_headers.get(index)?;
+        Some(Section {
+            index,
+            // Validated in `parse`
+            name: read_str(self.section_names, header.sh_name).unwrap_or_default(),
+            header,
+            data: section_data(self.bytes, &header),
+        })
+    }
+
+    pub fn sections(&self) -> impl Iterator<Item = Section<'a>> + '_ {
+        (0..self.section_headers.len()).filter_map(move |index| self.section(index))
+    }
+
+    pub fn section_by_name(&self, name: &str) -> Option<Section<'a>> {
+        self.sections().find(|section| section.name == name)
+    }
+
+    /// The section holding the program's instructions
+    pub fn text(&self) -> Option<Section<'a>> {
+        self.section_by_name(".text")
+    }
+
+    /// Read-only data referenced by the program, e.g. constant pubkeys
+    pub fn rodata(&self) -> Option<Section<'a>> {
+        self.section_by_name(".rodata")
+    }
+
+    /// Finds the section whose virtual range contains `address`
+    pub fn section_at_address(&self, address: u64) -> Option<Section<'a>> {
+        self.sections()
+            .filter(|section| section.header.sh_type != SHT_NULL)
+            .find(|section| section.contains_address(address))
+    }
+
+    /// Symbols from `.dynsym`, which survive stripping
+    pub fn dynamic_symbols(&self) -> Result<Vec<Symbol<'a>>, ElfError> {
+        self.symbols_of_type(SHT_DYNSYM)
+    }
+
+    /// Symbols from `.symtab`; empty for stripped binaries
+    pub fn symbols(&self) -> Result<Vec<Symbol<'a>>, ElfError> {
+        self.symbols_of_type(SHT_SYMTAB)
+    }
+
+    /// Every entry of every `SHT_REL` section
+    pub fn relocations(&self) -> impl Iterator<Item = Relocation> + '_ {
+        self.sections()
+            .filter(|section| section.header.sh_type == SHT_REL)
+            .flat_map(|section| section.data.chunks_exact(RELOCATION_SIZE))
+            .map(|entry| {
+                let info = u64::from_le_bytes(entry[8..16].try_into().unwrap());
+                Relocation {
+                    offset: u64::from_le_bytes(entry[..8].try_into().unwrap()),
+                    relocation_type: info as u32,
+                    symbol_index: (info >> 32) as u32,
+                }
+            })
+    }
+
+    fn symbols_of_type(&self, sh_type: u32) -> Result<Vec<Symbol<'a>>, ElfError> {
+        let table = match self.sections().find(|section| section.header.sh_type == sh_type) {
+            Some(table) => table,
+            None => return Ok(Vec::new()),
+        };
+        let strings = self
+            .section(table.header.sh_link as usize)
+            .ok_or(ElfError::InvalidSectionTable)?;
+
+        table
+            .data
+            .chunks_exact(SYMBOL_SIZE)
+            .map(|entry| {
+                Ok(Symbol {
+                    name: read_str(strings.data, read_u32(entry, 0)?)?,
+                    info: entry[4],
+                    shndx: read_u16(entry, 6)?,
+                    value: read_u64(entry, 8)?,
+                    size: read_u64(entry, 16)?,
+                })
+            })
+            .collect()
+    }
+}
+
+fn parse_section_header(entry: &[u8]) -> Result<SectionHeader, ElfError> {
+    Ok(SectionHeader {
+        sh_name: read_u32(entry, 0)?,
+        sh_type: read_u32(entry, 4)?,
+        sh_flags: read_u64(entry, 8)?,
+        sh_addr: read_u64(entry, 16)?,
+        sh_offset: read_u64(entry, 24)?,
+        sh_size: read_u64(entry, 32)?,
+        sh_link: read_u32(entry, 40)?,
+        sh_info: read_u32(entry, 44)?,
+        sh_entsize: read_u64(entry, 56)?,
+    })
+}
+
+/// Section contents; callers must have bounds-checked the header
+fn section_data<'a>(bytes: &'a [u8], header: &SectionHeader) -> &'a [u8] {
+    if header.sh_type == SHT_NOBITS {
+        return &[];
+    }
+    let start = header.sh_offset as usize;
+    &bytes[start..start + header.sh_size as usize]
+}
+
+fn to_usize(value: u64) -> Result<usize, ElfError> {
+    usize::try_from(value).map_err(|_| ElfError::OutOfBounds)
+}
+
+fn slice(bytes: &[u8], offset: usize, len: usize) -> Result<&[u8], ElfError> {
+    let end = offset.checked_add(len).ok_or(ElfError::OutOfBounds)?;
+    bytes.get(offset..end).ok_or(ElfError::OutOfBounds)
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, ElfError> {
+    Ok(u16::from_le_bytes(slice(bytes, offset, 2)?.try_into().unwrap()))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, ElfError> {
+    Ok(u32::from_le_bytes(slice(bytes, offset, 4)?.try_into().unwrap()))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, ElfError> {
+    Ok(u64::from_le_bytes(slice(bytes, offset, 8)?.try_into().unwrap()))
+}
+
+/// Reads a NUL-terminated string from a string table
+fn read_str(strings: &[u8], offset: u32) -> Result<&str, ElfError> {
+    let tail = strings.get(offset as usize..).ok_or(ElfError::InvalidString)?;
+    let len = tail.iter().position(|&b| b == 0).ok_or(ElfError::InvalidString)?;
+    std::str::from_utf8(&tail[..len]).map_err(|_| ElfError::InvalidString)
+}
+
+/// Builds small but well-formed SBF ELF files for tests
+#[cfg(test)]
+pub(crate) mod test_utils {
+    use super::*;
+
+    /// Virtual address (and file offset) of `.text` in built files
+    pub const TEXT_ADDRESS: u64 = 0x120;
+
+    const STB_GLOBAL: u8 = 1;
+
+    #[derive(Default)]
+    pub struct ElfBuilder {
+        text: Vec<u8>,
+        rodata: Vec<u8>,
+        flags: u32,
+        functions: Vec<(String, usize)>,
+        dynamic_symbols: Vec<(String, u8, u64)>,
+        relocations: Vec<(u64, u32, u32)>,
+    }
+
+    impl ElfBuilder {
+        pub fn new(text: &[u8]) -> Self {
+            Self {
+                text: text.to_vec(),
+                ..Self::default()
+            }
+        }
+
+        pub fn rodata(mut self, rodata: &[u8]) -> Self {
+            self.rodata = rodata.to_vec();
+            self
+        }
+
+        pub fn flags(mut self, flags: u32) -> Self {
+            self.flags = flags;
+            self
+        }
+
+        /// Adds a `.symtab` function symbol starting at instruction `pc`
+        pub fn function(mut self, name: &str, pc: usize) -> Self {
+            self.functions.push((name.to_string(), pc));
+            self
+        }
+
+        /// Marks the `call` at instruction `pc` as a relocated syscall
+        pub fn syscall(mut self, pc: usize, name: &str) -> Self {
+            let index = match self.dynamic_symbols.iter().position(|(n, _, _)| n == name) {
+                Some(index) => index,
+                None => {
+                    self.dynamic_symbols
+                        .push((name.to_string(), STB_GLOBAL << 4 | STT_NOTYPE, 0));
+                    self.dynamic_symbols.len() - 1
+                }
+            };
+            // Index 0 of the symbol table is the reserved null symbol
+            self.relocations.push((
+                TEXT_ADDRESS + pc as u64 * 8,
+                R_BPF_64_32,
+                index as u32 + 1,
+            ));
+            self
+        }
+
+        pub fn build(self) -> Vec<u8> {
+            let text_offset = TEXT_ADDRESS as usize;
+
+            let mut dynstr = vec![0u8];
+            let mut dynsym = vec![0u8; SYMBOL_SIZE];
+            for (name, info, value) in &self.dynamic_symbols {
+                push_symbol(&mut dynsym, &mut dynstr, name, *info, 0, *value);
+            }
+            let mut strtab = vec![0u8];
+            let mut symtab = vec![0u8; SYMBOL_SIZE];
+            for (name, pc) in &self.functions {
+                let value = TEXT_ADDRESS + *pc as u64 * 8;
+                push_symbol(&mut symtab, &mut strtab, name, STB_GLOBAL << 4 | STT_FUNC, 1, value);
+            }
+            let mut rel_dyn = Vec::new();
+            for (offset, relocation_type, symbol) in &self.relocations {
+                rel_dyn.extend_from_slice(&offset.to_le_bytes());
+                rel_dyn.extend_from_slice(&((*symbol as u64) << 32 | *relocation_type as u64).to_le_bytes());
+            }
+
+            // (name, type, link, data); `.text` and `.rodata` come first
+            let sections: Vec<(&str, u32, u32, Vec<u8>)> = vec![
+                (".text", SHT_PROGBITS, 0, self.text),
+                (".rodata", SHT_PROGBITS, 0, self.rodata),
+                (".dynsym", SHT_DYNSYM, 4, dynsym),
+                (".dynstr", SHT_STRTAB, 0, dynstr),
+                (".rel.dyn", SHT_REL, 3, rel_dyn),
+                (".symtab", SHT_SYMTAB, 7, symtab),
+                (".strtab", SHT_STRTAB, 0, strtab),
+                (".shstrtab", SHT_STRTAB, 0, Vec::new()),
+            ];
+            let mut shstrtab = vec![0u8];
+            let name_offsets: Vec<u32> = sections
+                .iter()
+                .map(|(name, ..)| {
+                    let offset = shstrtab.len() as u32;
+                    shstrtab.extend_from_slice(name.as_bytes());
+                    shstrtab.push(0);
+                    offset
+                })
+                .collect();
+
+            let mut bytes = vec![0u8; text_offset];
+            let mut headers = vec![0u8; SECTION_HEADER_SIZE];
+            for (index, (_, sh_type, link, data)) in sections.into_iter().enumerate() {
+                let data = if index == 7 { shstrtab.clone() } else { data };
+                let offset = align(bytes.len());
+                bytes.resize(offset, 0);
+                bytes.extend_from_slice(&data);
+
+                headers.extend_from_slice(&name_offsets[index].to_le_bytes());
+                headers.extend_from_slice(&sh_type.to_le_bytes());
+                headers.extend_from_slice(&0u64.to_le_bytes());
+                headers.extend_from_slice(&(offset as u64).to_le_bytes());
+                headers.extend_from_slice(&(offset as u64).to_le_bytes());
+                headers.extend_from_slice(&(data.len() as u64).to_le_bytes());
+                headers.extend_from_slice(&link.to_le_bytes());
+                headers.extend_from_slice(&0u32.to_le_bytes());
+                headers.extend_from_slice(&8u64.to_le_bytes());
+                headers.extend_from_slice(&0u64.to_le_bytes());
+            }
+
+            let shoff = align(bytes.len());
+            bytes.resize(shoff, 0);
+            bytes.extend_from_slice(&headers);
+
+            bytes[..4].copy_from_slice(&ELF_MAGIC);
+            bytes[4] = ELFCLASS64;
+            bytes[5] = ELFDATA2LSB;
+            bytes[6] = 1;
+            bytes[16..18].copy_from_slice(&3u16.to_le_bytes());
+            bytes[18..20].copy_from_slice(&EM_SBF.to_le_bytes());
+            bytes[20..24].copy_from_slice(&1u32.to_le_bytes());
+            bytes[24..32].copy_from_slice(&TEXT_ADDRESS.to_le_bytes());
+            bytes[40..48].copy_from_slice(&(shoff as u64).to_le_bytes());
+            bytes[48..52].copy_from_slice(&self.flags.to_le_bytes());
+            bytes[52..54].copy_from_slice(&(FILE_HEADER_SIZE as u16).to_le_bytes());
+            bytes[58..60].copy_from_slice(&(SECTION_HEADER_SIZE as u16).to_le_bytes());
+            bytes[60..62].copy_from_slice(&9u16.to_le_bytes());
+            bytes[62..64].copy_from_slice(&8u16.to_le_bytes());
+            bytes
+        }
+    }
+
+    fn align(offset: usize) -> usize {
+        (offset + 7) & !7
+    }
+
+    fn push_symbol(
+        table: &mut Vec<u8>,
+        strings: &mut Vec<u8>,
+        name: &str,
+        info: u8,
+        shndx: u16,
+        value: u64,
+    ) {
+        table.extend_from_slice(&(strings.len() as u32).to_le_bytes());
+        table.push(info);
+        table.push(0);
+        table.extend_from_slice(&shndx.to_le_bytes());
+        table.extend_from_slice(&value.to_le_bytes());
+        table.extend_from_slice(&0u64.to_le_bytes());
+        strings.extend_from_slice(name.as_bytes());
+        strings.push(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_utils::{ElfBuilder, TEXT_ADDRESS};
+    use super::*;
+
+    #[test]
+    fn test_parse_sections() {
+        let text = [0x95, 0, 0, 0, 0, 0, 0, 0];
+        let bytes = ElfBuilder::new(&text).rodata(b"constant").build();
+        let elf = Elf::parse(&bytes).unwrap();
+
+        assert_eq!(elf.header().e_machine, EM_SBF);
+        assert!(!elf.is_sbpf_v2());
+
+        let text_section = elf.text().unwrap();
+        assert_eq!(text_section.data, &text);
+        assert_eq!(text_section.header.sh_addr, TEXT_ADDRESS);
+        assert_eq!(elf.rodata().unwrap().data, b"constant");
+        assert_eq!(elf.section_at_address(TEXT_ADDRESS).unwrap().name, ".text");
+    }
+
+    #[test]
+    fn test_symbols_and_relocations() {
+        let text = [0u8; 24];
+        let bytes = ElfBuilder::new(&text)
+            .function("entrypoint", 0)
+            .function("helper", 2)
+            .syscall(1, "sol_log_")
+            .flags(EF_SBF_V2)
+            .build();
+        let elf = Elf::parse(&bytes).unwrap();
+        assert!(elf.is_sbpf_v2());
+        assert_eq!(elf.sbpf_version(), SbpfVersion::V2);
+
+        let symbols = elf.symbols().unwrap();
+        let helper = symbols.iter().find(|symbol| symbol.name == "helper").unwrap();
+        assert!(helper.is_function());
+        assert_eq!(helper.value, TEXT_ADDRESS + 16);
+
+        let dynamic_symbols = elf.dynamic_symbols().unwrap();
+        let relocations: Vec<_> = elf.relocations().collect();
+        assert_eq!(relocations.len(), 1);
+        assert_eq!(relocations[0].relocation_type, R_BPF_64_32);
+        assert_eq!(relocations[0].offset, TEXT_ADDRESS + 8);
+        assert_eq!(dynamic_symbols[relocations[0].symbol_index as usize].name, "sol_log_");
+    }
+
+    #[test]
+    fn test_rejects_malformed_files() {
+        assert_eq!(Elf::parse(&[0; 100]).unwrap_err(), ElfError::InvalidMagic);
+        assert_eq!(Elf::parse(&ELF_MAGIC).unwrap_err(), ElfError::OutOfBounds);
+
+        let mut bytes = ElfBuilder::new(&[0; 8]).build();
+        bytes[18..20].copy_from_slice(&62u16.to_le_bytes());
+        assert_eq!(Elf::parse(&bytes).unwrap_err(), ElfError::UnsupportedMachine(62));
+
+        // Section table pointing past the end of the file
+        let mut bytes = ElfBuilder::new(&[0; 8]).build();
+        let len = bytes.len() as u64;
+        bytes[40..48].copy_from_slice(&len.to_le_bytes());
+        assert_eq!(Elf::parse(&bytes).unwrap_err(), ElfError::OutOfBounds);
+    }
+}
@@ -0,0 +1,1490 @@
+// program/src/analysis/input.rs
+//! Tracks how a program accesses the serialized accounts it is invoked with
+//!
+//! The runtime hands the entrypoint a pointer (`r1`) to the serialized input:
+//! the account count followed by one record per account. Pointers into those
+//! records are followed through registers, stack spills and internal calls,
+//! starting at the entrypoint. Every load or store of a known account field is
+//! reported together with the fields that were read on all paths leading to it.
+//!
+//! Pointers are tracked relative to the start of *some* account record, so a
+//! loop walking from one record to the next keeps its precision. While all
+//! paths agree, a pointer also remembers which record it is in: the first one,
+//! or the one reached by stepping past the data of another at a given pc.
+//! Records reached at the same pc, like those of a loop, count as one. Programs that
+//! copy the records into other structures, like the Rust `entrypoint!`
+//! deserializer does, lose the trail and produce no accesses past the copy.
+//!
+//! Calls to `sol_invoke_signed_*` are reported with the origin of the invoked
+//! program id, followed through `sol_memcpy_` into the instruction on the stack.
+//! PDA derivations are reported with the shape of their seeds, where the bump
+//! seed of `sol_create_program_address` comes from, and whether a
+//! `sol_try_find_program_address` call on the same seeds, which yields the
+//! canonical bump, precedes them.
+//! Functions that zero an account's lamports while crediting another are
+//! reported along with whether they also wipe the data of the drained one.
+//! Sums and products of account data, lamports or instruction data that are
+//! written back to an account without being compared against a non-constant
+//! value, as checked arithmetic compares a sum with its operand, are reported
+//! as unchecked. The instruction data follows the last record, preceded by its
+//! length: records start with single byte flags, so a pointer stepped past the
+//! data of a record that reads a u64 at the start of the next one has reached
+//! the instruction data instead.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use super::{
+    cfg::{BlockExit, CallTarget, Cfg},
+    sbpf::{AluOp, DecodedInsn, Insn, JumpCondition, Operand, FRAME_POINTER},
+};
+
+/// Bytes an account's data may grow by during one instruction, reserved after
+/// the data of every record
+pub const MAX_PERMITTED_DATA_INCREASE: i64 = 10 * 1024;
+/// Offset of the first account record in the input region
+pub const FIRST_ACCOUNT_OFFSET: i64 = 8;
+/// Offset of the account data within a record
+pub const ACCOUNT_DATA_OFFSET: i64 = 88;
+/// Offset of the instruction data from the end of the last record, past its
+/// length
+pub const INSTRUCTION_DATA_OFFSET: i64 = 8;
+
+/// Upper bound on interprocedural passes over the call graph
+const MAX_PASSES: usize = 8;
+/// Upper bound on tracked stack slots per function
+const MAX_STACK_SLOTS: usize = 16;
+/// Upper bound on tracked seed sets of `sol_try_find_program_address` calls
+const MAX_SEED_SETS: usize = 4;
+/// Most seeds a PDA can be derived from
+const MAX_SEEDS: i64 = 16;
+/// Size of a seed slice: pointer and length
+const SEED_SIZE: i64 = 16;
+
+/// Offset of the program id pointer in `SolInstruction`
+const C_INSTRUCTION_PROGRAM_ID: i64 = 0;
+/// Offset of the inline program id in `StableInstruction`, after the account
+/// metas and data vectors
+const RUST_INSTRUCTION_PROGRAM_ID: i64 = 48;
+
+/// Fields of a serialized account record
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum AccountField {
+    /// `0xff`, or the index of the account this record duplicates
+    DuplicateMarker,
+    IsSigner,
+    IsWritable,
+    Executable,
+    OriginalDataLen,
+    Key,
+    Owner,
+    Lamports,
+    DataLen,
+    /// First 8 bytes of the account data, where account types usually keep
+    /// their discriminator
+    Discriminator,
+    /// Account data past the discriminator
+    Data,
+    /// Trailing field of the previous record; the account count when seen
+    /// from the first record
+    RentEpoch,
+}
+
+/// A set of `AccountField`s
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct FieldSet(u16);
+
+/// A load or store of an account field
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AccountAccess {
+    pub pc: usize,
+    /// Entry pc of the enclosing function
+    pub function: usize,
+    pub field: AccountField,
+    pub is_store: bool,
+    /// Fields loaded on every path from the entrypoint to this access
+    pub checked: FieldSet,
+    /// Fields compared on every path from the entrypoint to this access
+    pub compared: FieldSet,
+}
+
+/// Where the program id handed to `sol_invoke_signed_*` comes from
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ProgramIdSource {
+    /// Built from immediates or read-only data
+    Constant,
+    /// Copied from the key of an account the caller passed in
+    AccountKey,
+    /// Anything the analysis could not follow
+    Unknown,
+}
+
+/// A cross-program invocation
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Invoke {
+    pub pc: usize,
+    /// Entry pc of the enclosing function
+    pub function: usize,
+    pub program_id: ProgramIdSource,
+    /// Fields whose loaded value was compared on every path to the call
+    pub compared: FieldSet,
+}
+
+/// Syscall deriving a program address
+#[derive(BorshSerialize, BorshDeserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PdaSyscall {
+    /// `sol_create_program_address`, with the bump among the seeds
+    Create,
+    /// `sol_try_find_program_address`, which searches for the canonical bump
+    TryFind,
+}
+
+/// What the seeds of a derivation point at
+#[derive(BorshSerialize, BorshDeserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SeedsPattern {
+    /// Every seed is read-only data
+    Constant,
+    /// Some seed is built at runtime, e.g. a key or a bump on the stack
+    Dynamic,
+    /// The seeds could not be followed
+    Unknown,
+}
+
+/// Where the bump seed of a `sol_create_program_address` call comes from
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BumpSource {
+    /// Built from immediates or read-only data
+    Constant,
+    /// Read from account data, where programs keep the canonical bump
+    Stored,
+    /// Read from the input outside account data, e.g. an account key or
+    /// instruction data, so the caller picks it
+    CallerControlled,
+    /// Anything the analysis could not follow
+    Unknown,
+}
+
+/// A call deriving a program address
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PdaCall {
+    pub pc: usize,
+    /// Entry pc of the enclosing function
+    pub function: usize,
+    pub syscall: PdaSyscall,
+    pub seeds: SeedsPattern,
+    /// Origin of the bump, the last seed of a `sol_create_program_address`
+    pub bump: Option<BumpSource>,
+    /// A `sol_try_find_program_address` call on the same seeds, less the
+    /// bump, precedes this one on every path
+    pub bump_found: bool,
+}
+
+/// An `add64` or `mul64` on account data, lamports or instruction data whose
+/// result is stored to an account without being compared first; bounding it
+/// by a constant does not count, since that misses a wrapped result
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct UncheckedArithmetic {
+    /// Pc of the arithmetic instruction
+    pub pc: usize,
+    /// Entry pc of the function storing the result
+    pub function: usize,
+}
+
+/// A store zeroing an account's lamports in a function that also credits
+/// lamports, i.e. closes an account
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LamportDrain {
+    pub pc: usize,
+    /// Entry pc of the enclosing function
+    pub function: usize,
+    /// The function zeroes the data of the drained account, writes its
+    /// discriminator or `sol_memset_`s its data
+    pub data_wiped: bool,
+}
+
+/// Everything the analysis found
+#[derive(Debug, Clone, Default)]
+pub struct InputFacts {
+    /// Accesses to account fields, ordered by function and pc
+    pub accesses: Vec<AccountAccess>,
+    /// Invocations, ordered by function and pc
+    pub invokes: Vec<Invoke>,
+    /// Unchecked arithmetic, ordered by storing function
+    pub arithmetic: Vec<UncheckedArithmetic>,
+    /// PDA derivations, ordered by function and pc
+    pub pda_calls: Vec<PdaCall>,
+    /// Account closes, ordered by function and pc
+    pub lamport_drains: Vec<LamportDrain>,
+}
+
+/// Abstract value of a register or stack slot
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum Value {
+    Unknown,
+    Const(i64),
+    /// Read from constant memory, e.g. `.rodata`
+    Static,
+    /// Start of an account record plus an offset
+    Account(Record, i64),
+    /// Loaded from an account field
+    Field(AccountField),
+    /// End of an account's data plus an offset
+    DataEnd(Record, i64),
+    /// Frame pointer of the current function plus an offset
+    Stack(i64),
+    /// Start of the instruction data plus an offset
+    InstructionData(i64),
+    /// Loaded from the instruction data
+    Argument,
+    /// Result of the arithmetic instruction at the given pc on caller
+    /// controlled values, not yet compared
+    Arithmetic(usize),
+}
+
+/// Account record a pointer is relative to
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum Record {
+    /// The record following the account count
+    First,
+    /// The record reached by stepping past the data of another at the given pc
+    After(usize),
+    /// Paths disagree on the record
+    Any,
+}
+
+/// Must-facts about the paths reaching a point
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+struct Facts {
+    /// Fields loaded
+    checked: FieldSet,
+    /// Fields whose value reached a conditional jump or `sol_memcmp_`
+    compared: FieldSet,
+    /// Seed sets `sol_try_find_program_address` was called with
+    found_seeds: SeedSets,
+}
+
+/// Fingerprints of seed sets, up to `MAX_SEED_SETS` of them
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+struct SeedSets {
+    len: usize,
+    fingerprints: [u64; MAX_SEED_SETS],
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct State {
+    registers: [Value; 11],
+    /// Values spilled to the current frame, by offset and size
+    stack: Vec<(i64, usize, Value)>,
+    facts: Facts,
+}
+
+/// Facts gathered by one walk over a function
+#[derive(Default)]
+struct FunctionResult {
+    accesses: Vec<AccountAccess>,
+    invokes: Vec<Invoke>,
+    arithmetic: Vec<UncheckedArithmetic>,
+    pda_calls: Vec<PdaCall>,
+    lamport_drains: Vec<LamportDrain>,
+    /// Pcs of stores of zero to lamports and the record they hit
+    zeroed_lamports: Vec<(usize, Record)>,
+    /// Records other lamport stores hit
+    credited: Vec<Record>,
+    /// Records whose data is zeroed or whose discriminator is written
+    wiped: Vec<Record>,
+    /// Internal call targets and the state they are entered with
+    calls: Vec<(usize, State)>,
+    /// Facts holding on every return, `None` if the function never returns
+    exit_facts: Option<Facts>,
+}
+
+type Summaries = [(usize, Option<Facts>)];
+
+impl AccountField {
+    /// The field at `offset` from the start of a record
+    pub fn from_offset(offset: i64) -> Option<Self> {
+        Some(match offset {
+            -8..=-1 => AccountField::RentEpoch,
+            0 => AccountField::DuplicateMarker,
+            1 => AccountField::IsSigner,
+            2 => AccountField::IsWritable,
+            3 => AccountField::Executable,
+            4..=7 => AccountField::OriginalDataLen,
+            8..=39 => AccountField::Key,
+            40..=71 => AccountField::Owner,
+            72..=79 => AccountField::Lamports,
+            80..=87 => AccountField::DataLen,
+            ACCOUNT_DATA_OFFSET..=95 => AccountField::Discriminator,
+            96.. => AccountField::Data,
+            _ => return None,
+        })
+    }
+}
+
+impl FieldSet {
+    pub fn contains(&self, field: AccountField) -> bool {
+        self.0 & Self::bit(field) != 0
+    }
+
+    pub fn insert(&mut self, field: AccountField) {
+        self.0 |= Self::bit(field);
+    }
+
+    fn bit(field: AccountField) -> u16 {
+        1 << field as u16
+    }
+
+    fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+}
+
+impl Facts {
+    fn union(self, other: Self) -> Self {
+        Self {
+            checked: self.checked.union(other.checked),
+            compared: self.compared.union(other.compared),
+            found_seeds: self.found_seeds.union(other.found_seeds),
+        }
+    }
+
+    fn intersection(self, other: Self) -> Self {
+        Self {
+            checked: self.checked.intersection(other.checked),
+            compared: self.compared.intersection(other.compared),
+            found_seeds: self.found_seeds.intersection(other.found_seeds),
+        }
+    }
+}
+
+impl SeedSets {
+    fn contains(&self, fingerprint: u64) -> bool {
+        self.fingerprints[..self.len].contains(&fingerprint)
+    }
+
+    fn insert(&mut self, fingerprint: u64) {
+        if !self.contains(fingerprint) && self.len < MAX_SEED_SETS {
+            self.fingerprints[self.len] = fingerprint;
+            self.len += 1;
+        }
+    }
+
+    fn union(mut self, other: Self) -> Self {
+        for &fingerprint in &other.fingerprints[..other.len] {
+            self.insert(fingerprint);
+        }
+        self
+    }
+
+    fn intersection(self, other: Self) -> Self {
+        let mut common = Self::default();
+        for &fingerprint in &self.fingerprints[..self.len] {
+            if other.contains(fingerprint) {
+                common.insert(fingerprint);
+            }
+        }
+        common
+    }
+}
+
+impl Value {
+    fn join(self, other: Self) -> Self {
+        match (self, other) {
+            _ if self == other => self,
+            (Value::Account(record, offset), Value::Account(other_record, other_offset)) if offset == other_offset => {
+                Value::Account(record.join(other_record), offset)
+            }
+            (Value::DataEnd(record, offset), Value::DataEnd(other_record, other_offset)) if offset == other_offset => {
+                Value::DataEnd(record.join(other_record), offset)
+            }
+            _ => Value::Unknown,
+        }
+    }
+
+    fn add(self, other: Self) -> Self {
+        match (self, other) {
+            (Value::Const(a), Value::Const(b)) => Value::Const(a.wrapping_add(b)),
+            (Value::Account(record, offset), Value::Const(c)) | (Value::Const(c), Value::Account(record, offset)) => {
+                Value::Account(record, offset.wrapping_add(c))
+            }
+            (Value::DataEnd(record, offset), Value::Const(c)) | (Value::Const(c), Value::DataEnd(record, offset)) => {
+                Value::DataEnd(record, offset.wrapping_add(c))
+            }
+            (Value::Stack(offset), Value::Const(c)) | (Value::Const(c), Value::Stack(offset)) => {
+                Value::Stack(offset.wrapping_add(c))
+            }
+            (Value::InstructionData(offset), Value::Const(c)) | (Value::Const(c), Value::InstructionData(offset)) => {
+                Value::InstructionData(offset.wrapping_add(c))
+            }
+            // Skipping over the data of the record
+            (Value::Account(record, offset), Value::Field(AccountField::DataLen))
+            | (Value::Field(AccountField::DataLen), Value::Account(record, offset)) => {
+                Value::DataEnd(record, offset.wrapping_sub(ACCOUNT_DATA_OFFSET))
+            }
+            _ => Value::Unknown,
+        }
+    }
+
+    /// `self & other` computed at `pc`
+    fn and(self, other: Self, pc: usize) -> Self {
+        match (self, other) {
+            (Value::Const(a), Value::Const(b)) => Value::Const(a & b),
+            // Records start 8-byte aligned
+            (Value::Account(record, offset), Value::Const(-8)) => Value::Account(record, offset & -8),
+            // `(data_end + MAX_PERMITTED_DATA_INCREASE + 7) & -8` is the rent
+            // epoch of the record, 8 bytes before the next one
+            (Value::DataEnd(_, offset), Value::Const(-8)) => {
+                match offset.checked_sub(MAX_PERMITTED_DATA_INCREASE + 7) {
+                    Some(past_padding) if past_padding >= 0 && past_padding % 8 == 0 => {
+                        Value::Account(Record::After(pc), past_padding - 8)
+                    }
+                    _ => Value::Unknown,
+                }
+            }
+            _ => Value::Unknown,
+        }
+    }
+
+    /// Returns true for amounts a caller controls through account contents
+    /// or instruction data
+    fn is_caller_controlled(self) -> bool {
+        matches!(
+            self,
+            Value::Field(AccountField::Discriminator | AccountField::Data | AccountField::Lamports)
+                | Value::Argument
+                | Value::Arithmetic(_)
+        )
+    }
+
+    fn is_constant(self) -> bool {
+        matches!(self, Value::Const(_) | Value::Static)
+    }
+
+    /// Field a pointer value points at
+    fn field(self) -> Option<AccountField> {
+        match self {
+            Value::Account(_, offset) => AccountField::from_offset(offset),
+            _ => None,
+        }
+    }
+
+    /// Record a pointer value points into
+    fn record(self) -> Record {
+        match self {
+            Value::Account(record, _) | Value::DataEnd(record, _) => record,
+            _ => Record::Any,
+        }
+    }
+}
+
+impl Record {
+    fn join(self, other: Self) -> Self {
+        if self == other {
+            self
+        } else {
+            Record::Any
+        }
+    }
+
+    /// Returns true if both are known to be the same record
+    fn is_same(self, other: Self) -> bool {
+        self != Record::Any && self == other
+    }
+}
+
+impl State {
+    fn unknown() -> Self {
+        let mut registers = [Value::Unknown; 11];
+        registers[FRAME_POINTER as usize] = Value::Stack(0);
+        Self {
+            registers,
+            stack: Vec::new(),
+            facts: Facts::default(),
+        }
+    }
+
+    /// State of the entrypoint, whose `r1` points at the account count
+    fn entrypoint() -> Self {
+        let mut state = Self::unknown();
+        state.registers[1] = Value::Account(Record::First, -FIRST_ACCOUNT_OFFSET);
+        state
+    }
+
+    /// State a callee starts with: the arguments in `r1`-`r5` and a fresh frame
+    fn callee(&self) -> Self {
+        let mut state = Self::unknown();
+        for register in 1..=5 {
+            state.registers[register] = match self.registers[register] {
+                // Offsets into the caller's frame mean nothing in the callee's
+                Value::Stack(_) => Value::Unknown,
+                value => value,
+            };
+        }
+        state.facts = self.facts;
+        state
+    }
+
+    /// Joins `other` into `self`, returning true if `self` changed
+    fn join(&mut self, other: &Self) -> bool {
+        let before = self.clone();
+        for (register, value) in self.registers.iter_mut().zip(other.registers) {
+            *register = register.join(value);
+        }
+        self.stack = self
+            .stack
+            .iter()
+            .filter_map(|&(offset, size, value)| {
+                let &(_, _, other_value) = other
+                    .stack
+                    .iter()
+                    .find(|&&(other_offset, other_size, _)| other_offset == offset && other_size == size)?;
+                match value.join(other_value) {
+                    Value::Unknown => None,
+                    value => Some((offset, size, value)),
+                }
+            })
+            .collect();
+        self.facts = self.facts.intersection(other.facts);
+        *self != before
+    }
+
+    fn operand(&self, operand: Operand) -> Value {
+        match operand {
+            Operand::Reg(register) => self.register(register),
+            Operand::Imm(imm) => Value::Const(imm),
+        }
+    }
+
+    fn register(&self, register: u8) -> Value {
+        self.registers.get(register as usize).copied().unwrap_or(Value::Unknown)
+    }
+
+    fn set_register(&mut self, register: u8, value: Value) {
+        // r10 is read-only
+        if register != FRAME_POINTER {
+            if let Some(slot) = self.registers.get_mut(register as usize) {
+                *slot = value;
+            }
+        }
+    }
+
+    fn spill(&mut self, offset: i64, size: usize, value: Value) {
+        // Offsets come from the analyzed code; bytes running past `i64::MAX`
+        // are not tracked but still overwrite the slots they reach. Tracked
+        // slots always end within range.
+        let end = offset.checked_add(size as i64);
+        self.stack.retain(|&(slot, slot_size, _)| {
+            slot + slot_size as i64 <= offset || end.is_some_and(|end| slot >= end)
+        });
+        if end.is_some() && self.stack.len() < MAX_STACK_SLOTS {
+            self.stack.push((offset, size, value));
+        }
+    }
+
+    /// Value of the `size` bytes spilled at `offset`
+    fn reload(&self, offset: i64, size: usize) -> Value {
+        self.stack
+            .iter()
+            .find(|&&(slot, slot_size, _)| slot == offset && slot_size == size)
+            .map_or(Value::Unknown, |&(_, _, value)| value)
+    }
+
+    /// Reinterprets pointers into `record`, which turned out to be past the
+    /// last one, as pointers into the instruction data. Without a known
+    /// record only copies of `pointer` are.
+    fn enter_instruction_data(&mut self, record: Record, pointer: Value) {
+        let stack = self.stack.iter_mut().map(|(_, _, value)| value);
+        for value in self.registers.iter_mut().chain(stack) {
+            if let Value::Account(other, offset) = *value {
+                if other.is_same(record) || *value == pointer {
+                    *value = Value::InstructionData(offset.wrapping_sub(INSTRUCTION_DATA_OFFSET));
+                }
+            }
+        }
+    }
+
+    /// Marks the fields `value` was loaded from, or points at, as compared
+    fn compare(&mut self, value: Value) {
+        let field = match value {
+            Value::Field(field) => Some(field),
+            // A copy of the field on the stack
+            Value::Stack(offset) => match self.reload(offset, 8) {
+                Value::Field(field) => Some(field),
+                _ => None,
+            },
+            value => value.field(),
+        };
+        if let Some(field) = field {
+            self.facts.compared.insert(field);
+        }
+    }
+
+    /// Forgets an arithmetic result once it has been compared
+    fn check_arithmetic(&mut self, value: Value) {
+        if let Value::Arithmetic(_) = value {
+            for register in &mut self.registers {
+                if *register == value {
+                    *register = Value::Unknown;
+                }
+            }
+            self.stack.retain(|&(_, _, slot)| slot != value);
+        }
+    }
+
+    fn clobber_caller_saved(&mut self) {
+        for register in &mut self.registers[..=5] {
+            *register = Value::Unknown;
+        }
+    }
+}
+
+/// Runs the analysis from the entrypoint of `cfg`
+pub fn analyze(cfg: &Cfg) -> InputFacts {
+    let mut entries = vec![(cfg.entrypoint(), State::entrypoint())];
+    let mut summaries: Vec<(usize, Option<Facts>)> = Vec::new();
+
+    for _ in 0..MAX_PASSES {
+        let mut changed = false;
+        let mut index = 0;
+        // Callees discovered during the pass are appended and walked in it
+        while index < entries.len() {
+            let (entry, state) = entries[index].clone();
+            let result = analyze_function(cfg, entry, state, &summaries);
+
+            for (target, state) in result.calls {
+                match entries.iter_mut().find(|(entry, _)| *entry == target) {
+                    Some((_, existing)) => changed |= existing.join(&state),
+                    None => {
+                        entries.push((target, state));
+                        changed = true;
+                    }
+                }
+            }
+            match summaries.iter_mut().find(|(function, _)| *function == entry) {
+                Some((_, summary)) if *summary == result.exit_facts => {}
+                Some((_, summary)) => {
+                    *summary = result.exit_facts;
+                    changed = true;
+                }
+                None => {
+                    summaries.push((entry, result.exit_facts));
+                    changed = true;
+                }
+            }
+            index += 1;
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    entries.sort_unstable_by_key(|(entry, _)| *entry);
+    let mut facts = InputFacts::default();
+    for (entry, state) in entries {
+        let result = analyze_function(cfg, entry, state, &summaries);
+        facts.accesses.extend(result.accesses);
+        facts.invokes.extend(result.invokes);
+        facts.pda_calls.extend(result.pda_calls);
+        facts.lamport_drains.extend(result.lamport_drains);
+        for arithmetic in result.arithmetic {
+            if !facts.arithmetic.contains(&arithmetic) {
+                facts.arithmetic.push(arithmetic);
+            }
+        }
+    }
+    facts
+}
+
+/// Computes the state at every block of the function, then walks each block
+/// once more to collect its facts
+fn analyze_function(cfg: &Cfg, entry: usize, entry_state: State, summaries: &Summaries) -> FunctionResult {
+    let mut states: Vec<(usize, State)> = vec![(entry, entry_state)];
+    let mut worklist = vec![entry];
+
+    while let Some(start) = worklist.pop() {
+        let Some(block) = cfg.block_at(start) else {
+            continue;
+        };
+        let mut state = states.iter().find(|(pc, _)| *pc == start).unwrap().1.clone();
+        walk_block(cfg, entry, block.start, block.end, &mut state, summaries, &mut FunctionResult::default());
+
+        for successor in block.successors() {
+            // Running into another function leaves this one
+            if successor >= cfg.slot_count() || (successor != entry && cfg.is_function_entry(successor)) {
+                continue;
+            }
+            match states.iter_mut().find(|(pc, _)| *pc == successor) {
+                Some((_, existing)) => {
+                    if existing.join(&state) && !worklist.contains(&successor) {
+                        worklist.push(successor);
+                    }
+                }
+                None => {
+                    states.push((successor, state.clone()));
+                    worklist.push(successor);
+                }
+            }
+        }
+    }
+
+    states.sort_unstable_by_key(|(start, _)| *start);
+    let mut result = FunctionResult::default();
+    for (start, mut state) in states {
+        if let Some(block) = cfg.block_at(start) {
+            walk_block(cfg, entry, block.start, block.end, &mut state, summaries, &mut result);
+            if block.exit == BlockExit::Return {
+                let facts = result
+                    .exit_facts
+                    .map_or(state.facts, |facts| facts.intersection(state.facts));
+                result.exit_facts = Some(facts);
+            }
+        }
+    }
+    // A drain credits a record not known to be the one it zeroes; only wipes
+    // of the drained record count
+    result.lamport_drains = result
+        .zeroed_lamports
+        .iter()
+        .filter(|&&(_, drained)| result.credited.iter().any(|&credited| !credited.is_same(drained)))
+        .map(|&(pc, drained)| LamportDrain {
+            pc,
+            function: entry,
+            data_wiped: result.wiped.iter().any(|&wiped| wiped.is_same(drained)),
+        })
+        .collect();
+    result
+}
+
+fn walk_block(
+    cfg: &Cfg,
+    function: usize,
+    start: usize,
+    end: usize,
+    state: &mut State,
+    summaries: &Summaries,
+    result: &mut FunctionResult,
+) {
+    let decoder = cfg.decoder();
+    let mut pc = start;
+    while pc < end {
+        let Some(insn) = decoder.decode_at(pc) else {
+            return;
+        };
+        step(cfg, function, &insn, state, summaries, result);
+        pc = insn.next_pc();
+    }
+}
+
+fn step(
+    cfg: &Cfg,
+    function: usize,
+    decoded: &DecodedInsn,
+    state: &mut State,
+    summaries: &Summaries,
+    result: &mut FunctionResult,
+) {
+    let access = |result: &mut FunctionResult, field: AccountField, is_store: bool, facts: Facts| {
+        result.accesses.push(AccountAccess {
+            pc: decoded.pc,
+            function,
+            field,
+            is_store,
+            checked: facts.checked,
+            compared: facts.compared,
+        });
+    };
+
+    match decoded.insn {
+        Insn::LoadImm64 { dst, imm } => state.set_register(dst, Value::Const(imm as i64)),
+        Insn::Load { size, dst, src, off } => {
+            let base = state.register(src);
+            let value = match base.add(Value::Const(off as i64)) {
+                Value::Stack(offset) => state.reload(offset, size.bytes()),
+                Value::Const(_) | Value::Static => Value::Static,
+                Value::InstructionData(offset) if offset >= 0 => Value::Argument,
+                Value::InstructionData(_) => Value::Unknown,
+                // No field spans the first 8 bytes of a record, so this is the
+                // instruction data length
+                Value::Account(record, 0) if size.bytes() == 8 => {
+                    state.enter_instruction_data(record, base);
+                    Value::Unknown
+                }
+                pointer => match pointer.field() {
+                    Some(field) => {
+                        access(result, field, false, state.facts);
+                        state.facts.checked.insert(field);
+                        Value::Field(field)
+                    }
+                    None => Value::Unknown,
+                },
+            };
+            state.set_register(dst, value);
+        }
+        Insn::Store { size, dst, off, src } => {
+            let value = state.operand(src);
+            match state.register(dst).add(Value::Const(off as i64)) {
+                Value::Stack(offset) => state.spill(offset, size.bytes(), value),
+                pointer => {
+                    if let Some(field) = pointer.field() {
+                        access(result, field, true, state.facts);
+                        if let Value::Arithmetic(pc) = value {
+                            result.arithmetic.push(UncheckedArithmetic { pc, function });
+                        }
+                        let record = pointer.record();
+                        match (field, value) {
+                            (AccountField::Lamports, Value::Const(0)) => {
+                                result.zeroed_lamports.push((decoded.pc, record))
+                            }
+                            (AccountField::Lamports, _) => result.credited.push(record),
+                            (AccountField::Discriminator, _) | (AccountField::Data, Value::Const(0)) => {
+                                result.wiped.push(record)
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+        Insn::Alu { op, is_64, dst, src } => {
+            let lhs = state.register(dst);
+            let rhs = state.operand(src);
+            let value = match op {
+                AluOp::Mov if is_64 => rhs,
+                AluOp::Mov => match rhs {
+                    Value::Const(value) => Value::Const(value as u32 as i64),
+                    _ => Value::Unknown,
+                },
+                _ if !is_64 => Value::Unknown,
+                AluOp::Add | AluOp::Mul if lhs.is_caller_controlled() || rhs.is_caller_controlled() => {
+                    Value::Arithmetic(decoded.pc)
+                }
+                AluOp::Add => lhs.add(rhs),
+                AluOp::Sub => match rhs {
+                    Value::Const(value) => lhs.add(Value::Const(value.wrapping_neg())),
+                    _ => Value::Unknown,
+                },
+                AluOp::And => lhs.and(rhs, decoded.pc),
+                _ => Value::Unknown,
+            };
+            state.set_register(dst, value);
+        }
+        Insn::Jump { cond, dst, src, .. } => {
+            if cond != JumpCondition::Always {
+                let (lhs, rhs) = (state.register(dst), state.operand(src));
+                state.compare(lhs);
+                state.compare(rhs);
+                if !rhs.is_constant() {
+                    state.check_arithmetic(lhs);
+                }
+                if !lhs.is_constant() {
+                    state.check_arithmetic(rhs);
+                }
+            }
+        }
+        Insn::Call { .. } => {
+            match cfg.call_target(decoded) {
+                Some(CallTarget::Internal(target)) if target < cfg.slot_count() => {
+                    result.calls.push((target, state.callee()));
+                    let summary = summaries
+                        .iter()
+                        .find(|(function, _)| *function == target)
+                        .and_then(|(_, summary)| *summary);
+                    if let Some(facts) = summary {
+                        state.facts = state.facts.union(facts);
+                    }
+                }
+                Some(CallTarget::External(name)) => match name {
+                    "sol_memcpy_" | "sol_memmove_" => {
+                        if let Some(field) = state.register(2).field() {
+                            access(result, field, false, state.facts);
+                            state.facts.checked.insert(field);
+                        }
+                        copy(state);
+                    }
+                    "sol_memset_" => {
+                        let pointer = state.register(1);
+                        if matches!(pointer.field(), Some(AccountField::Discriminator | AccountField::Data)) {
+                            result.wiped.push(pointer.record());
+                        }
+                    }
+                    "sol_memcmp_" => {
+                        state.compare(state.register(1));
+                        state.compare(state.register(2));
+                    }
+                    "sol_invoke_signed_c" | "sol_invoke_signed_rust" => {
+                        result.invokes.push(Invoke {
+                            pc: decoded.pc,
+                            function,
+                            program_id: program_id_source(state, name == "sol_invoke_signed_c"),
+                            compared: state.facts.compared,
+                        });
+                    }
+                    "sol_create_program_address" | "sol_try_find_program_address" => {
+                        let seeds = seeds(state);
+                        let (syscall, searched, bump) = match (name, seeds.as_deref()) {
+                            ("sol_try_find_program_address", seeds) => (PdaSyscall::TryFind, seeds, None),
+                            // The bump is the last seed
+                            (_, Some([searched @ .., (bump, _)])) => {
+                                (PdaSyscall::Create, Some(searched), Some(bump_source(state, *bump)))
+                            }
+                            _ => (PdaSyscall::Create, None, Some(BumpSource::Unknown)),
+                        };
+                        let searched = searched.and_then(fingerprint);
+                        result.pda_calls.push(PdaCall {
+                            pc: decoded.pc,
+                            function,
+                            syscall,
+                            seeds: seeds_pattern(seeds.as_deref()),
+                            bump,
+                            bump_found: searched.is_some_and(|searched| state.facts.found_seeds.contains(searched)),
+                        });
+                        if syscall == PdaSyscall::TryFind {
+                            if let Some(searched) = searched {
+                                state.facts.found_seeds.insert(searched);
+                            }
+                            // The canonical bump is written through `r5`
+                            if let Value::Stack(bump) = state.register(5) {
+                                state.spill(bump, 1, Value::Unknown);
+                            }
+                        }
+                    }
+                    _ => {}
+                },
+                _ => {}
+            }
+            state.clobber_caller_saved();
+        }
+        Insn::CallReg { .. } => state.clobber_caller_saved(),
+        Insn::Exit | Insn::Invalid { .. } => {}
+    }
+}
+
+/// Applies `sol_memcpy_(dst, src, len)` to the tracked stack slots
+fn copy(state: &mut State) {
+    let (Value::Stack(dst), Value::Const(len)) = (state.register(1), state.register(3)) else {
+        return;
+    };
+    let src = state.register(2);
+    for offset in (0..len.clamp(0, 8 * MAX_STACK_SLOTS as i64)).step_by(8) {
+        let value = match src.add(Value::Const(offset)) {
+            Value::Stack(slot) => state.reload(slot, 8),
+            Value::Const(_) | Value::Static => Value::Static,
+            Value::InstructionData(_) => Value::Argument,
+            pointer => pointer.field().map_or(Value::Unknown, Value::Field),
+        };
+        let Some(slot) = dst.checked_add(offset) else {
+            return;
+        };
+        state.spill(slot, 8, value);
+    }
+}
+
+/// Pointer and length of each seed slice `r1` points at, `r2` of them
+fn seeds(state: &State) -> Option<Vec<(Value, Value)>> {
+    let (Value::Stack(seeds), Value::Const(count @ 0..=MAX_SEEDS)) = (state.register(1), state.register(2)) else {
+        return None;
+    };
+    let seed = |index: i64| {
+        let slice = seeds.checked_add(index * SEED_SIZE)?;
+        Some((state.reload(slice, 8), state.reload(slice.checked_add(8)?, 8)))
+    };
+    (0..count).map(seed).collect()
+}
+
+/// Classifies what `seeds` point at
+fn seeds_pattern(seeds: Option<&[(Value, Value)]>) -> SeedsPattern {
+    let Some(seeds) = seeds else {
+        return SeedsPattern::Unknown;
+    };
+    let mut pattern = SeedsPattern::Constant;
+    for (pointer, _) in seeds {
+        match pointer {
+            Value::Const(_) | Value::Static => {}
+            Value::Unknown => return SeedsPattern::Unknown,
+            _ => pattern = SeedsPattern::Dynamic,
+        }
+    }
+    pattern
+}
+
+/// Identifies a seed set by the pointers and lengths of its seeds, unless
+/// some seed could not be followed
+fn fingerprint(seeds: &[(Value, Value)]) -> Option<u64> {
+    if seeds.iter().any(|(pointer, _)| *pointer == Value::Unknown) {
+        return None;
+    }
+    let mut hasher = DefaultHasher::new();
+    seeds.hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+/// Classifies the bump byte `pointer` points at
+fn bump_source(state: &State, pointer: Value) -> BumpSource {
+    let bump = match pointer {
+        Value::Stack(offset) => state.reload(offset, 1),
+        Value::Const(_) | Value::Static => return BumpSource::Constant,
+        Value::InstructionData(_) => return BumpSource::CallerControlled,
+        pointer => pointer.field().map_or(Value::Unknown, Value::Field),
+    };
+    match bump {
+        Value::Const(_) | Value::Static => BumpSource::Constant,
+        Value::Field(AccountField::Discriminator | AccountField::Data) => BumpSource::Stored,
+        Value::Field(_) | Value::Argument => BumpSource::CallerControlled,
+        _ => BumpSource::Unknown,
+    }
+}
+
+/// Classifies the program id of the instruction `r1` points at
+fn program_id_source(state: &State, is_c: bool) -> ProgramIdSource {
+    let Value::Stack(instruction) = state.register(1) else {
+        return ProgramIdSource::Unknown;
+    };
+    // Offsets past `i64::MAX` hold nothing tracked
+    let reload = |offset: i64| instruction.checked_add(offset).map_or(Value::Unknown, |slot| state.reload(slot, 8));
+    let classify = |value: Value| match value {
+        Value::Const(_) | Value::Static => ProgramIdSource::Constant,
+        Value::Field(AccountField::Key) => ProgramIdSource::AccountKey,
+        _ => ProgramIdSource::Unknown,
+    };
+
+    if is_c {
+        // A pointer to the program id
+        return match reload(C_INSTRUCTION_PROGRAM_ID) {
+            Value::Account(_, offset) if AccountField::from_offset(offset) == Some(AccountField::Key) => {
+                ProgramIdSource::AccountKey
+            }
+            value => classify(value),
+        };
+    }
+
+    // The 32 bytes of the program id
+    let mut sources = (0..4).map(|word| classify(reload(RUST_INSTRUCTION_PROGRAM_ID + word * 8)));
+    let first = sources.next().unwrap_or(ProgramIdSource::Unknown);
+    if sources.all(|source| source == first) {
+        first
+    } else {
+        ProgramIdSource::Unknown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        cfg::ExternalCall,
+        sbpf::{test_utils::slot, SbpfVersion},
+    };
+    use super::*;
+
+    fn accesses(text: &[u8]) -> Vec<AccountAccess> {
+        analyze(&Cfg::new(text, SbpfVersion::V1, 0, [], Vec::new())).accesses
+    }
+
+    #[test]
+    fn test_field_offsets() {
+        assert_eq!(AccountField::from_offset(1), Some(AccountField::IsSigner));
+        assert_eq!(AccountField::from_offset(40), Some(AccountField::Owner));
+        assert_eq!(AccountField::from_offset(72), Some(AccountField::Lamports));
+        assert_eq!(AccountField::from_offset(88), Some(AccountField::Discriminator));
+        assert_eq!(AccountField::from_offset(1000), Some(AccountField::Data));
+        assert_eq!(AccountField::from_offset(-9), None);
+    }
+
+    #[test]
+    fn test_checked_fields() {
+        let text = [
+            slot(0x15, 2, 0, 1, 0),  // 0: jeq r2, 0, +1
+            slot(0x71, 3, 1, 9, 0),  // 1: ldxb r3, [r1+0x9]       is_signer
+            slot(0x7b, 1, 2, 80, 0), // 2: stxdw [r1+0x50], r2     lamports
+            slot(0x71, 3, 1, 9, 0),  // 3: ldxb r3, [r1+0x9]
+            slot(0x7b, 1, 2, 80, 0), // 4: stxdw [r1+0x50], r2
+            slot(0x95, 0, 0, 0, 0),  // 5: exit
+        ]
+        .concat();
+        let accesses = accesses(&text);
+        let stores: Vec<_> = accesses.iter().filter(|access| access.is_store).collect();
+
+        assert_eq!(accesses[0].field, AccountField::IsSigner);
+        assert_eq!(stores.len(), 2);
+        assert_eq!(stores[0].field, AccountField::Lamports);
+        // The taken branch reaches the first store without loading `is_signer`
+        assert!(!stores[0].checked.contains(AccountField::IsSigner));
+        assert!(stores[1].checked.contains(AccountField::IsSigner));
+    }
+
+    #[test]
+    fn test_calls_and_spills() {
+        let text = [
+            slot(0x07, 1, 0, 0, 8),    // 0: add64 r1, 8             first account
+            slot(0x7b, 10, 1, -8, 0),  // 1: stxdw [r10-0x8], r1
+            slot(0xb7, 1, 0, 0, 0),    // 2: mov64 r1, 0
+            slot(0x79, 1, 10, -8, 0),  // 3: ldxdw r1, [r10-0x8]
+            slot(0x85, 0, 1, 0, 1),    // 4: call +1
+            slot(0x95, 0, 0, 0, 0),    // 5: exit
+            slot(0x7a, 1, 0, 96, 7),   // 6: stdw [r1+0x60], 7       account data
+            slot(0x95, 0, 0, 0, 0),    // 7: exit
+        ]
+        .concat();
+        let accesses = accesses(&text);
+        assert_eq!(accesses.len(), 1);
+        assert_eq!(accesses[0].pc, 6);
+        assert_eq!(accesses[0].function, 6);
+        assert_eq!(accesses[0].field, AccountField::Data);
+        assert!(accesses[0].is_store);
+        assert_eq!(accesses[0].checked, FieldSet::default());
+    }
+
+    #[test]
+    fn test_stack_offsets_near_overflow() {
+        let text = [
+            slot(0xbf, 6, 10, 0, 0),        // 0: mov64 r6, r10
+            slot(0x18, 3, 0, 0, -1),        // 1: lddw r3, 0x7fffffffffffffff
+            slot(0, 0, 0, 0, 0x7fff_ffff),
+            slot(0x0f, 6, 3, 0, 0),         // 3: add64 r6, r3
+            slot(0x7b, 6, 4, 0, 0),         // 4: stxdw [r6+0x0], r4
+            slot(0xbf, 1, 6, 0, 0),         // 5: mov64 r1, r6
+            slot(0xb7, 3, 0, 0, 16),        // 6: mov64 r3, 16
+            slot(0x85, 0, 1, 0, -1),        // 7: call sol_memcpy_
+            slot(0xbf, 1, 6, 0, 0),         // 8: mov64 r1, r6
+            slot(0xb7, 2, 0, 0, 1),         // 9: mov64 r2, 1
+            slot(0x85, 0, 1, 0, -1),        // 10: call sol_create_program_address
+            slot(0xbf, 1, 6, 0, 0),         // 11: mov64 r1, r6
+            slot(0x85, 0, 1, 0, -1),        // 12: call sol_invoke_signed_rust
+            slot(0xbf, 1, 6, 0, 0),         // 13: mov64 r1, r6
+            slot(0x85, 0, 1, 0, -1),        // 14: call sol_invoke_signed_c
+            slot(0x95, 0, 0, 0, 0),         // 15: exit
+        ]
+        .concat();
+        let external_calls = vec![
+            ExternalCall { pc: 7, name: "sol_memcpy_" },
+            ExternalCall { pc: 10, name: "sol_create_program_address" },
+            ExternalCall { pc: 12, name: "sol_invoke_signed_rust" },
+            ExternalCall { pc: 14, name: "sol_invoke_signed_c" },
+        ];
+        // Offsets past `i64::MAX` are given up on instead of overflowing
+        let facts = analyze(&Cfg::new(&text, SbpfVersion::V1, 0, [], external_calls));
+        assert_eq!(facts.pda_calls[0].seeds, SeedsPattern::Unknown);
+        assert_eq!(facts.pda_calls[0].bump, Some(BumpSource::Unknown));
+        assert!(facts.invokes.iter().all(|invoke| invoke.program_id == ProgramIdSource::Unknown));
+    }
+
+    #[test]
+    fn test_record_offsets_near_overflow() {
+        let text = [
+            slot(0x79, 2, 1, 88, 0),        // 0: ldxdw r2, [r1+0x58]    data_len
+            slot(0x18, 4, 0, 0, 8),         // 1: lddw r4, i64::MIN + 8
+            slot(0, 0, 0, 0, i32::MIN),
+            slot(0xbf, 3, 1, 0, 0),         // 3: mov64 r3, r1
+            slot(0x0f, 3, 4, 0, 0),         // 4: add64 r3, r4           i64::MIN past the record
+            slot(0x0f, 3, 2, 0, 0),         // 5: add64 r3, r2
+            slot(0xbf, 5, 1, 0, 0),         // 6: mov64 r5, r1
+            slot(0x0f, 5, 4, 0, 0),         // 7: add64 r5, r4
+            slot(0x07, 5, 0, 0, 88),        // 8: add64 r5, 88
+            slot(0x0f, 5, 2, 0, 0),         // 9: add64 r5, r2           data end at i64::MIN
+            slot(0x57, 5, 0, 0, -8),        // 10: and64 r5, -8
+            slot(0x7b, 5, 2, 80, 0),        // 11: stxdw [r5+0x50], r2
+            slot(0x95, 0, 0, 0, 0),         // 12: exit
+        ]
+        .concat();
+        // Data ends that wrap around lead to no record
+        let accesses = accesses(&text);
+        assert_eq!(accesses.len(), 1);
+        assert_eq!(accesses[0].field, AccountField::DataLen);
+    }
+
+    #[test]
+    fn test_walk_to_next_account() {
+        let text = [
+            slot(0x79, 2, 1, 88, 0),    // 0: ldxdw r2, [r1+0x58]    data_len of account 0
+            slot(0xbf, 3, 1, 0, 0),     // 1: mov64 r3, r1
+            slot(0x07, 3, 0, 0, 96),    // 2: add64 r3, 96           its data
+            slot(0x0f, 3, 2, 0, 0),     // 3: add64 r3, r2           end of its data
+            slot(0x07, 3, 0, 0, 10247), // 4: add64 r3, 10247
+            slot(0x57, 3, 0, 0, -8),    // 5: and64 r3, -8           its rent epoch
+            slot(0x71, 4, 3, 9, 0),     // 6: ldxb r4, [r3+0x9]      is_signer of account 1
+            slot(0x95, 0, 0, 0, 0),     // 7: exit
+        ]
+        .concat();
+        let accesses = accesses(&text);
+        assert_eq!(accesses.len(), 2);
+        assert_eq!(accesses[0].field, AccountField::DataLen);
+        assert_eq!(accesses[1].pc, 6);
+        assert_eq!(accesses[1].field, AccountField::IsSigner);
+    }
+
+    fn invokes(text: &[u8], external_calls: Vec<ExternalCall>) -> Vec<Invoke> {
+        analyze(&Cfg::new(text, SbpfVersion::V1, 0, [], external_calls)).invokes
+    }
+
+    #[test]
+    fn test_invoke_c() {
+        let text = [
+            slot(0xbf, 2, 1, 0, 0),      // 0: mov64 r2, r1
+            slot(0x07, 2, 0, 0, 16),     // 1: add64 r2, 16           key of account 0
+            slot(0x7b, 10, 2, -64, 0),   // 2: stxdw [r10-0x40], r2   instruction.program_id
+            slot(0xbf, 1, 10, 0, 0),     // 3: mov64 r1, r10
+            slot(0x07, 1, 0, 0, -64),    // 4: add64 r1, -64
+            slot(0x85, 0, 1, 0, -1),     // 5: call sol_invoke_signed_c
+            slot(0x18, 2, 0, 0, 0x1000), // 6: lddw r2, 0x1000        .rodata
+            slot(0, 0, 0, 0, 0),
+            slot(0x7b, 10, 2, -64, 0),   // 8: stxdw [r10-0x40], r2
+            slot(0xbf, 1, 10, 0, 0),     // 9: mov64 r1, r10
+            slot(0x07, 1, 0, 0, -64),    // 10: add64 r1, -64
+            slot(0x85, 0, 1, 0, -1),     // 11: call sol_invoke_signed_c
+            slot(0x95, 0, 0, 0, 0),      // 12: exit
+        ]
+        .concat();
+        let external_calls = vec![
+            ExternalCall { pc: 5, name: "sol_invoke_signed_c" },
+            ExternalCall { pc: 11, name: "sol_invoke_signed_c" },
+        ];
+        let invokes = invokes(&text, external_calls);
+        assert_eq!(invokes.len(), 2);
+        assert_eq!(invokes[0].pc, 5);
+        assert_eq!(invokes[0].program_id, ProgramIdSource::AccountKey);
+        assert_eq!(invokes[1].program_id, ProgramIdSource::Constant);
+    }
+
+    #[test]
+    fn test_invoke_rust() {
+        let text = [
+            slot(0xbf, 2, 1, 0, 0),     // 0: mov64 r2, r1
+            slot(0x07, 2, 0, 0, 16),    // 1: add64 r2, 16            key of account 0
+            slot(0xbf, 1, 10, 0, 0),    // 2: mov64 r1, r10
+            slot(0x07, 1, 0, 0, -32),   // 3: add64 r1, -32           instruction.program_id
+            slot(0xb7, 3, 0, 0, 32),    // 4: mov64 r3, 32
+            slot(0x85, 0, 1, 0, -1),    // 5: call sol_memcpy_
+            slot(0x79, 4, 10, -32, 0),  // 6: ldxdw r4, [r10-0x20]
+            slot(0x55, 4, 0, 1, 0),     // 7: jne r4, 0, +1
+            slot(0x95, 0, 0, 0, 0),     // 8: exit
+            slot(0xbf, 1, 10, 0, 0),    // 9: mov64 r1, r10
+            slot(0x07, 1, 0, 0, -80),   // 10: add64 r1, -80
+            slot(0x85, 0, 1, 0, -1),    // 11: call sol_invoke_signed_rust
+            slot(0x95, 0, 0, 0, 0),     // 12: exit
+        ]
+        .concat();
+        let external_calls = vec![
+            ExternalCall { pc: 5, name: "sol_memcpy_" },
+            ExternalCall { pc: 11, name: "sol_invoke_signed_rust" },
+        ];
+        let cfg = Cfg::new(&text, SbpfVersion::V1, 0, [], external_calls);
+        let facts = analyze(&cfg);
+        assert_eq!(facts.accesses[0].pc, 5);
+        assert_eq!(facts.accesses[0].field, AccountField::Key);
+        assert_eq!(facts.invokes.len(), 1);
+        assert_eq!(facts.invokes[0].program_id, ProgramIdSource::AccountKey);
+        // The copied key reached a branch before the call
+        assert!(facts.invokes[0].compared.contains(AccountField::Key));
+
+        // Without the branch the key is never compared
+        let mut unchecked = text.clone();
+        unchecked[7 * 8..9 * 8].copy_from_slice(&[slot(0x05, 0, 0, 1, 0), slot(0x95, 0, 0, 0, 0)].concat());
+        let external_calls = vec![
+            ExternalCall { pc: 5, name: "sol_memcpy_" },
+            ExternalCall { pc: 11, name: "sol_invoke_signed_rust" },
+        ];
+        let invokes = invokes(&unchecked, external_calls);
+        assert!(!invokes[0].compared.contains(AccountField::Key));
+    }
+
+    #[test]
+    fn test_unchecked_arithmetic() {
+        let text = [
+            slot(0x79, 2, 1, 96, 0),   // 0: ldxdw r2, [r1+0x60]    first u64 of account data
+            slot(0x07, 2, 0, 0, 5),    // 1: add64 r2, 5
+            slot(0x7b, 1, 2, 96, 0),   // 2: stxdw [r1+0x60], r2
+            slot(0x79, 3, 1, 80, 0),   // 3: ldxdw r3, [r1+0x50]    lamports
+            slot(0x2f, 3, 2, 0, 0),    // 4: mul64 r3, r2
+            slot(0xa5, 3, 0, 0, 10),   // 5: jlt r3, 10, +0
+            slot(0x7b, 1, 3, 80, 0),   // 6: stxdw [r1+0x50], r3
+            slot(0x95, 0, 0, 0, 0),    // 7: exit
+        ]
+        .concat();
+        let facts = analyze(&Cfg::new(&text, SbpfVersion::V1, 0, [], Vec::new()));
+        // A bound on the product does not catch it wrapping around
+        assert_eq!(
+            facts.arithmetic,
+            vec![UncheckedArithmetic { pc: 1, function: 0 }, UncheckedArithmetic { pc: 4, function: 0 }]
+        );
+
+        // Compared with an operand, as checked arithmetic does, it is stored safely
+        let mut checked = text.clone();
+        checked[5 * 8..6 * 8].copy_from_slice(&slot(0xad, 3, 2, 0, 0)); // 5: jlt r3, r2, +0
+        let facts = analyze(&Cfg::new(&checked, SbpfVersion::V1, 0, [], Vec::new()));
+        assert_eq!(facts.arithmetic, vec![UncheckedArithmetic { pc: 1, function: 0 }]);
+    }
+
+    #[test]
+    fn test_instruction_data_arithmetic() {
+        let text = [
+            slot(0x79, 2, 1, 88, 0),    // 0: ldxdw r2, [r1+0x58]    data_len of account 0
+            slot(0xbf, 3, 1, 0, 0),     // 1: mov64 r3, r1
+            slot(0x07, 3, 0, 0, 96),    // 2: add64 r3, 96           its data
+            slot(0x0f, 3, 2, 0, 0),     // 3: add64 r3, r2           end of its data
+            slot(0x07, 3, 0, 0, 10247), // 4: add64 r3, 10247
+            slot(0x57, 3, 0, 0, -8),    // 5: and64 r3, -8           its rent epoch
+            slot(0x79, 4, 3, 8, 0),     // 6: ldxdw r4, [r3+0x8]     instruction data length
+            slot(0x79, 5, 3, 16, 0),    // 7: ldxdw r5, [r3+0x10]    first u64 of instruction data
+            slot(0x07, 5, 0, 0, 5),     // 8: add64 r5, 5
+            slot(0x7b, 1, 5, 96, 0),    // 9: stxdw [r1+0x60], r5
+            slot(0x95, 0, 0, 0, 0),     // 10: exit
+        ]
+        .concat();
+        let facts = analyze(&Cfg::new(&text, SbpfVersion::V1, 0, [], Vec::new()));
+        // The instruction data is not mistaken for the key of another account
+        assert_eq!(facts.accesses.len(), 2);
+        assert_eq!(facts.accesses[1].pc, 9);
+        assert_eq!(facts.arithmetic, vec![UncheckedArithmetic { pc: 8, function: 0 }]);
+
+        // A byte at the start of the next record is its duplicate marker
+        let mut record = text.clone();
+        record[6 * 8..7 * 8].copy_from_slice(&slot(0x71, 4, 3, 8, 0)); // 6: ldxb r4, [r3+0x8]
+        let facts = analyze(&Cfg::new(&record, SbpfVersion::V1, 0, [], Vec::new()));
+        assert_eq!(facts.accesses[1].field, AccountField::DuplicateMarker);
+        assert_eq!(facts.accesses[2].field, AccountField::Key);
+        assert!(facts.arithmetic.is_empty());
+    }
+
+    #[test]
+    fn test_pda_calls() {
+        let text = [
+            slot(0x18, 2, 0, 0, 0x1000), // 0: lddw r2, 0x1000        seed in .rodata
+            slot(0, 0, 0, 0, 0),
+            slot(0x7b, 10, 2, -32, 0),   // 2: stxdw [r10-0x20], r2
+            slot(0xbf, 1, 10, 0, 0),     // 3: mov64 r1, r10
+            slot(0x07, 1, 0, 0, -32),    // 4: add64 r1, -32
+            slot(0xb7, 2, 0, 0, 1),      // 5: mov64 r2, 1
+            slot(0x85, 0, 1, 0, -1),     // 6: call sol_try_find_program_address
+            slot(0xbf, 3, 10, 0, 0),     // 7: mov64 r3, r10
+            slot(0x07, 3, 0, 0, -1),     // 8: add64 r3, -1           bump on the stack
+            slot(0x7b, 10, 3, -16, 0),   // 9: stxdw [r10-0x10], r3
+            slot(0xbf, 1, 10, 0, 0),     // 10: mov64 r1, r10
+            slot(0x07, 1, 0, 0, -32),    // 11: add64 r1, -32
+            slot(0xb7, 2, 0, 0, 2),      // 12: mov64 r2, 2
+            slot(0x85, 0, 1, 0, -1),     // 13: call sol_create_program_address
+            slot(0x95, 0, 0, 0, 0),      // 14: exit
+        ]
+        .concat();
+        let external_calls = vec![
+            ExternalCall { pc: 6, name: "sol_try_find_program_address" },
+            ExternalCall { pc: 13, name: "sol_create_program_address" },
+        ];
+        let cfg = Cfg::new(&text, SbpfVersion::V1, 0, [], external_calls.clone());
+        let calls = analyze(&cfg).pda_calls;
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].syscall, PdaSyscall::TryFind);
+        assert_eq!(calls[0].seeds, SeedsPattern::Constant);
+        assert!(!calls[0].bump_found);
+        assert_eq!(calls[1].syscall, PdaSyscall::Create);
+        assert_eq!(calls[1].seeds, SeedsPattern::Dynamic);
+        assert!(calls[1].bump_found);
+
+        // A search on other seeds says nothing about this bump
+        let mut unrelated = text.clone();
+        unrelated[5 * 8..6 * 8].copy_from_slice(&slot(0xb7, 2, 0, 0, 0)); // 5: mov64 r2, 0
+        let cfg = Cfg::new(&unrelated, SbpfVersion::V1, 0, [], external_calls);
+        assert!(!analyze(&cfg).pda_calls[1].bump_found);
+    }
+
+    #[test]
+    fn test_bump_sources() {
+        // Derives from the key of account 0 and the bump `r2` points at
+        let derive = |bump: Vec<Vec<u8>>| {
+            let call = 3 + bump.len() + 4;
+            let text = [
+                vec![
+                    slot(0xbf, 2, 1, 0, 0),    // mov64 r2, r1
+                    slot(0x07, 2, 0, 0, 16),   // add64 r2, 16           key of account 0
+                    slot(0x7b, 10, 2, -32, 0), // stxdw [r10-0x20], r2
+                ],
+                bump,
+                vec![
+                    slot(0x7b, 10, 2, -16, 0), // stxdw [r10-0x10], r2
+                    slot(0xbf, 1, 10, 0, 0),   // mov64 r1, r10
+                    slot(0x07, 1, 0, 0, -32),  // add64 r1, -32
+                    slot(0xb7, 2, 0, 0, 2),    // mov64 r2, 2
+                    slot(0x85, 0, 1, 0, -1),   // call sol_create_program_address
+                    slot(0x95, 0, 0, 0, 0),    // exit
+                ],
+            ]
+            .concat()
+            .concat();
+            let external_calls = vec![ExternalCall { pc: call, name: "sol_create_program_address" }];
+            analyze(&Cfg::new(&text, SbpfVersion::V1, 0, [], external_calls)).pda_calls[0].bump
+        };
+
+        // Pointing into account data
+        let stored = derive(vec![slot(0xbf, 2, 1, 0, 0), slot(0x07, 2, 0, 0, 0x68)]);
+        assert_eq!(stored, Some(BumpSource::Stored));
+        // Pointing into the input outside account data
+        let supplied = derive(vec![slot(0xbf, 2, 1, 0, 0), slot(0x07, 2, 0, 0, 0x18)]);
+        assert_eq!(supplied, Some(BumpSource::CallerControlled));
+        // Copied to the stack first
+        let copied = derive(vec![
+            slot(0x71, 3, 1, 0x18, 0), // ldxb r3, [r1+0x18]
+            slot(0x73, 10, 3, -1, 0),  // stxb [r10-0x1], r3
+            slot(0xbf, 2, 10, 0, 0),   // mov64 r2, r10
+            slot(0x07, 2, 0, 0, -1),   // add64 r2, -1
+        ]);
+        assert_eq!(copied, Some(BumpSource::CallerControlled));
+        let unknown = derive(vec![slot(0xbf, 2, 10, 0, 0), slot(0x07, 2, 0, 0, -1)]);
+        assert_eq!(unknown, Some(BumpSource::Unknown));
+    }
+
+    #[test]
+    fn test_lamport_drains() {
+        let close = |credit: Vec<u8>, wipe: Vec<u8>| {
+            [
+                slot(0x79, 3, 1, 88, 0),    // 0: ldxdw r3, [r1+0x58]    data_len of account 0
+                slot(0xbf, 4, 1, 0, 0),     // 1: mov64 r4, r1
+                slot(0x07, 4, 0, 0, 96),    // 2: add64 r4, 96
+                slot(0x0f, 4, 3, 0, 0),     // 3: add64 r4, r3
+                slot(0x07, 4, 0, 0, 10247), // 4: add64 r4, 10247
+                slot(0x57, 4, 0, 0, -8),    // 5: and64 r4, -8           account 1
+                slot(0x79, 2, 1, 80, 0),    // 6: ldxdw r2, [r1+0x50]    lamports of account 0
+                slot(0x7a, 1, 0, 80, 0),    // 7: stdw [r1+0x50], 0
+                credit,                     // 8
+                wipe,                       // 9
+                slot(0x95, 0, 0, 0, 0),     // 10: exit
+            ]
+            .concat()
+        };
+        let drains = |text: &[u8]| analyze(&Cfg::new(text, SbpfVersion::V1, 0, [], Vec::new())).lamport_drains;
+        // stxdw [r4+0x50], r2: lamports of account 1
+        let credit = || slot(0x7b, 4, 2, 80, 0);
+        // mov64 r0, 0
+        let nop = || slot(0xb7, 0, 0, 0, 0);
+
+        let drained = drains(&close(credit(), nop()));
+        assert_eq!(drained, vec![LamportDrain { pc: 7, function: 0, data_wiped: false }]);
+
+        // stdw [r1+0x60], -1: a closed-account discriminator
+        let wiped = drains(&close(credit(), slot(0x7a, 1, 0, 96, -1)));
+        assert!(wiped[0].data_wiped);
+
+        // stdw [r4+0x60], 1: initializing account 1 leaves account 0 intact
+        let other_wiped = drains(&close(credit(), slot(0x7a, 4, 0, 96, 1)));
+        assert_eq!(other_wiped, drained);
+
+        // stxdw [r1+0x50], r2: putting the lamports back closes nothing
+        assert!(drains(&close(slot(0x7b, 1, 2, 80, 0), nop())).is_empty());
+    }
+
+    #[test]
+    fn test_record_join() {
+        let first = Value::Account(Record::First, 8);
+        let next = Value::Account(Record::After(5), 8);
+        assert_eq!(first.join(first), first);
+        assert_eq!(first.join(next), Value::Account(Record::Any, 8));
+        assert_eq!(first.join(Value::Account(Record::First, 16)), Value::Unknown);
+        assert!(!Record::Any.is_same(Record::Any));
+        assert!(Record::After(5).is_same(Record::After(5)));
+    }
+}
@@ -0,0 +1,230 @@
+// program/src/analysis/loader.rs
+//! Account layouts of the BPF Upgradeable Loader
+//!
+//! An upgradeable program account only stores the address of its
+//! ProgramData account, which holds a metadata header followed by the ELF.
+//! Both layouts are bincode-encoded `UpgradeableLoaderState` variants.
+
+use solana_program::{bpf_loader_upgradeable::UpgradeableLoaderState, pubkey::Pubkey};
+
+use crate::constants::{
+    GOKI_SMART_WALLET_PROGRAM_ID, MULTISIG_SIGNER_INDICES, SERUM_MULTISIG_PROGRAM_ID, SQUADS_V3_PROGRAM_ID,
+    SQUADS_V4_PROGRAM_ID,
+};
+
+const PROGRAM_TAG: u32 = 2;
+const PROGRAM_DATA_TAG: u32 = 3;
+
+/// Size of the ProgramData header preceding the ELF bytes
+pub const PROGRAM_DATA_METADATA_SIZE: usize = UpgradeableLoaderState::size_of_programdata_metadata();
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProgramDataHeader {
+    /// Slot of the last deployment or upgrade
+    pub slot: u64,
+    /// Account allowed to upgrade the program; `None` once frozen
+    pub upgrade_authority: Option<Pubkey>,
+}
+
+/// Who can replace the analyzed code
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UpgradeAuthorityKind {
+    /// No upgrade authority, the code can never change
+    Immutable,
+    /// A single key can upgrade the program at will
+    SingleKey,
+    /// The authority signs for a multisig of a known multisig program
+    Multisig,
+}
+
+impl UpgradeAuthorityKind {
+    /// `multisig` is the address and owner of the multisig account the caller
+    /// claims controls the authority, when supplied
+    pub fn classify(upgrade_authority: Option<&Pubkey>, multisig: Option<(&Pubkey, &Pubkey)>) -> Self {
+        match (upgrade_authority, multisig) {
+            (None, _) => UpgradeAuthorityKind::Immutable,
+            (Some(authority), Some((multisig, owner))) if is_multisig_signer(authority, multisig, owner) => {
+                UpgradeAuthorityKind::Multisig
+            }
+            (Some(_), _) => UpgradeAuthorityKind::SingleKey,
+        }
+    }
+}
+
+/// Whether `authority` is an address `multisig`, an account of the multisig
+/// program `owner`, signs with once its members approve
+pub fn is_multisig_signer(authority: &Pubkey, multisig: &Pubkey, owner: &Pubkey) -> bool {
+    let derives = |seeds: &[&[u8]]| Pubkey::find_program_address(seeds, owner).0 == *authority;
+    let any_index = |derives_at: &dyn Fn(u8) -> bool| (0..MULTISIG_SIGNER_INDICES).any(derives_at);
+    match *owner {
+        SQUADS_V3_PROGRAM_ID => any_index(&|index| {
+            derives(&[b"squad", multisig.as_ref(), &u32::from(index).to_le_bytes(), b"authority"])
+        }),
+        SQUADS_V4_PROGRAM_ID => any_index(&|index| derives(&[b"multisig", multisig.as_ref(), b"vault", &[index]])),
+        SERUM_MULTISIG_PROGRAM_ID => derives(&[multisig.as_ref()]),
+        // Smart wallets sign themselves, or through derived wallets
+        GOKI_SMART_WALLET_PROGRAM_ID => {
+            authority == multisig
+                || any_index(&|index| {
+                    derives(&[b"GokiSmartWalletDerived", multisig.as_ref(), &u64::from(index).to_le_bytes()])
+                })
+        }
+        _ => false,
+    }
+}
+
+/// Reads the ProgramData address out of an upgradeable program account
+pub fn programdata_address(program_account_data: &[u8]) -> Option<Pubkey> {
+    if program_account_data.len() < UpgradeableLoaderState::size_of_program()
+        || read_tag(program_account_data)? != PROGRAM_TAG
+    {
+        return None;
+    }
+    Some(Pubkey::new_from_array(program_account_data[4..36].try_into().ok()?))
+}
+
+impl ProgramDataHeader {
+    pub fn parse(programdata: &[u8]) -> Option<Self> {
+        if programdata.len() < PROGRAM_DATA_METADATA_SIZE || read_tag(programdata)? != PROGRAM_DATA_TAG {
+            return None;
+        }
+        let slot = u64::from_le_bytes(programdata[4..12].try_into().ok()?);
+        let upgrade_authority = match programdata[12] {
+            0 => None,
+            1 => Some(Pubkey::new_from_array(programdata[13..45].try_into().ok()?)),
+            _ => return None,
+        };
+        Some(Self {
+            slot,
+            upgrade_authority,
+        })
+    }
+}
+
+/// The executable bytes stored after the ProgramData header
+pub fn executable_bytes(programdata: &[u8]) -> &[u8] {
+    programdata.get(PROGRAM_DATA_METADATA_SIZE..).unwrap_or_default()
+}
+
+fn read_tag(data: &[u8]) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(..4)?.try_into().ok()?))
+}
+
+/// Serializes loader accounts for tests
+#[cfg(test)]
+pub(crate) mod test_utils {
+    use super::*;
+
+    pub fn program_account(programdata_address: &Pubkey) -> Vec<u8> {
+        let mut data = PROGRAM_TAG.to_le_bytes().to_vec();
+        data.extend_from_slice(programdata_address.as_ref());
+        data
+    }
+
+    pub fn programdata_account(slot: u64, upgrade_authority: Option<Pubkey>, elf: &[u8]) -> Vec<u8> {
+        let mut data = PROGRAM_DATA_TAG.to_le_bytes().to_vec();
+        data.extend_from_slice(&slot.to_le_bytes());
+        match upgrade_authority {
+            Some(authority) => {
+                data.push(1);
+                data.extend_from_slice(authority.as_ref());
+            }
+            None => data.extend_from_slice(&[0; 33]),
+        }
+        data.extend_from_slice(elf);
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_utils::*;
+    use super::*;
+
+    #[test]
+    fn test_programdata_address() {
+        let address = Pubkey::new_unique();
+        assert_eq!(programdata_address(&program_account(&address)), Some(address));
+
+        // Truncated accounts and other loader states are rejected
+        assert_eq!(programdata_address(&program_account(&address)[..20]), None);
+        let mut buffer = program_account(&address);
+        buffer[0] = 1;
+        assert_eq!(programdata_address(&buffer), None);
+    }
+
+    #[test]
+    fn test_programdata_header() {
+        let authority = Pubkey::new_unique();
+        let data = programdata_account(42, Some(authority), b"\x7fELF");
+        let header = ProgramDataHeader::parse(&data).unwrap();
+        assert_eq!(header.slot, 42);
+        assert_eq!(header.upgrade_authority, Some(authority));
+        assert_eq!(executable_bytes(&data), b"\x7fELF");
+
+        let frozen = programdata_account(7, None, &[]);
+        assert_eq!(ProgramDataHeader::parse(&frozen).unwrap().upgrade_authority, None);
+        assert_eq!(ProgramDataHeader::parse(&program_account(&authority)), None);
+    }
+
+    #[test]
+    fn test_classify_upgrade_authority() {
+        let authority = Pubkey::new_unique();
+        let multisig = Pubkey::new_unique();
+        let (vault, _) =
+            Pubkey::find_program_address(&[b"multisig", multisig.as_ref(), b"vault", &[0]], &SQUADS_V4_PROGRAM_ID);
+        let system_program = solana_program::system_program::id();
+
+        assert_eq!(
+            UpgradeAuthorityKind::classify(None, Some((&multisig, &SQUADS_V4_PROGRAM_ID))),
+            UpgradeAuthorityKind::Immutable
+        );
+        assert_eq!(
+            UpgradeAuthorityKind::classify(Some(&authority), None),
+            UpgradeAuthorityKind::SingleKey
+        );
+        assert_eq!(
+            UpgradeAuthorityKind::classify(Some(&vault), Some((&multisig, &SQUADS_V4_PROGRAM_ID))),
+            UpgradeAuthorityKind::Multisig
+        );
+        // Any key next to an account of a multisig program proves nothing
+        assert_eq!(
+            UpgradeAuthorityKind::classify(Some(&authority), Some((&multisig, &SQUADS_V4_PROGRAM_ID))),
+            UpgradeAuthorityKind::SingleKey
+        );
+        assert_eq!(
+            UpgradeAuthorityKind::classify(Some(&vault), Some((&multisig, &SQUADS_V3_PROGRAM_ID))),
+            UpgradeAuthorityKind::SingleKey
+        );
+        assert_eq!(
+            UpgradeAuthorityKind::classify(Some(&vault), Some((&multisig, &system_program))),
+            UpgradeAuthorityKind::SingleKey
+        );
+    }
+
+    #[test]
+    fn test_multisig_signers() {
+        let multisig = Pubkey::new_unique();
+        let derive = |seeds: &[&[u8]], program| Pubkey::find_program_address(seeds, program).0;
+
+        let squads_v3 = derive(&[b"squad", multisig.as_ref(), &1u32.to_le_bytes(), b"authority"], &SQUADS_V3_PROGRAM_ID);
+        assert!(is_multisig_signer(&squads_v3, &multisig, &SQUADS_V3_PROGRAM_ID));
+        let serum = derive(&[multisig.as_ref()], &SERUM_MULTISIG_PROGRAM_ID);
+        assert!(is_multisig_signer(&serum, &multisig, &SERUM_MULTISIG_PROGRAM_ID));
+        assert!(is_multisig_signer(&multisig, &multisig, &GOKI_SMART_WALLET_PROGRAM_ID));
+        let goki = derive(
+            &[b"GokiSmartWalletDerived", multisig.as_ref(), &2u64.to_le_bytes()],
+            &GOKI_SMART_WALLET_PROGRAM_ID,
+        );
+        assert!(is_multisig_signer(&goki, &multisig, &GOKI_SMART_WALLET_PROGRAM_ID));
+
+        // Only Goki smart wallets sign themselves
+        assert!(!is_multisig_signer(&multisig, &multisig, &SQUADS_V4_PROGRAM_ID));
+        // and indices are searched up to a bound
+        let far_vault = derive(
+            &[b"multisig", multisig.as_ref(), b"vault", &[MULTISIG_SIGNER_INDICES]],
+            &SQUADS_V4_PROGRAM_ID,
+        );
+        assert!(!is_multisig_signer(&far_vault, &multisig, &SQUADS_V4_PROGRAM_ID));
+    }
+}
@@ -0,0 +1,10 @@
+// program/src/analysis/mod.rs
+//! Static analysis of SBF program binaries
+
+pub mod cfg;
+pub mod compute;
+pub mod elf;
+pub mod input;
+pub mod loader;
+pub mod sbpf;
+pub mod syscalls;
@@ -0,0 +1,651 @@
+// program/src/analysis/sbpf.rs
+//! sBPF instruction decoder and disassembler
+//!
+//! Instructions are 8-byte slots (`opcode, dst|src<<4, off: i16, imm: i32`),
+//! except `lddw` which spans two slots. Program counters count slots, so the
+//! second half of an `lddw` occupies a pc of its own and is never yielded.
+
+use std::fmt;
+
+pub const INSN_SIZE: usize = 8;
+
+// Instruction classes (low three opcode bits)
+pub const BPF_LD: u8 = 0x00;
+pub const BPF_LDX: u8 = 0x01;
+pub const BPF_ST: u8 = 0x02;
+pub const BPF_STX: u8 = 0x03;
+pub const BPF_ALU32: u8 = 0x04;
+pub const BPF_JMP: u8 = 0x05;
+pub const BPF_PQR: u8 = 0x06;
+pub const BPF_ALU64: u8 = 0x07;
+
+// Frequently matched full opcodes
+pub const LD_DW_IMM: u8 = 0x18;
+pub const CALL_IMM: u8 = 0x85;
+pub const CALL_REG: u8 = 0x8d;
+pub const EXIT: u8 = 0x95;
+
+/// Register holding the read-only frame pointer
+pub const FRAME_POINTER: u8 = 10;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SbpfVersion {
+    V1,
+    V2,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OpcodeClass {
+    Ld,
+    Ldx,
+    St,
+    Stx,
+    Alu32,
+    Jmp,
+    Pqr,
+    Alu64,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MemSize {
+    Byte,
+    Half,
+    Word,
+    DoubleWord,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AluOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Or,
+    And,
+    Lsh,
+    Rsh,
+    Neg,
+    Mod,
+    Xor,
+    Mov,
+    Arsh,
+    /// Byte swap to little-endian; the immediate holds the bit width
+    Le,
+    /// Byte swap to big-endian; the immediate holds the bit width
+    Be,
+    /// SBFv2 "high or", replaces the upper half of `lddw`
+    Hor,
+    Uhmul,
+    Udiv,
+    Urem,
+    Lmul,
+    Shmul,
+    Sdiv,
+    Srem,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum JumpCondition {
+    Always,
+    Eq,
+    Gt,
+    Ge,
+    Set,
+    Ne,
+    Sgt,
+    Sge,
+    Lt,
+    Le,
+    Slt,
+    Sle,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Operand {
+    Reg(u8),
+    Imm(i64),
+}
+
+/// Raw fields of an instruction slot
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RawInsn {
+    pub opcode: u8,
+    pub dst: u8,
+    pub src: u8,
+    pub off: i16,
+    pub imm: i32,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Insn {
+    /// `lddw dst, imm`, the only 16-byte instruction
+    LoadImm64 { dst: u8, imm: u64 },
+    /// `ldx dst, [src+off]`
+    Load { size: MemSize, dst: u8, src: u8, off: i16 },
+    /// `st [dst+off], imm` or `stx [dst+off], src`
+    Store { size: MemSize, dst: u8, off: i16, src: Operand },
+    Alu { op: AluOp, is_64: bool, dst: u8, src: Operand },
+    /// Jumps are relative to the following instruction
+    Jump { cond: JumpCondition, dst: u8, src: Operand, off: i16 },
+    /// `call imm`: a syscall hash, or with `src == 1` a relative function call
+    Call { src: u8, imm: i32 },
+    /// `callx reg`
+    CallReg { reg: u8 },
+    Exit,
+    Invalid { opcode: u8 },
+}
+
+/// An instruction together with its position in `.text`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DecodedInsn {
+    pub pc: usize,
+    pub raw: RawInsn,
+    pub insn: Insn,
+}
+
+/// Iterates over the instructions of a `.text` section
+#[derive(Debug, Clone)]
+pub struct Decoder<'a> {
+    text: &'a [u8],
+    version: SbpfVersion,
+    pc: usize,
+}
+
+impl MemSize {
+    pub fn bytes(&self) -> usize {
+        match self {
+            MemSize::Byte => 1,
+            MemSize::Half => 2,
+            MemSize::Word => 4,
+            MemSize::DoubleWord => 8,
+        }
+    }
+
+    fn from_opcode(opcode: u8) -> Self {
+        match opcode & 0x18 {
+            0x00 => MemSize::Word,
+            0x08 => MemSize::Half,
+            0x10 => MemSize::Byte,
+            _ => MemSize::DoubleWord,
+        }
+    }
+
+    fn suffix(&self) -> &'static str {
+        match self {
+            MemSize::Byte => "b",
+            MemSize::Half => "h",
+            MemSize::Word => "w",
+            MemSize::DoubleWord => "dw",
+        }
+    }
+}
+
+impl RawInsn {
+    pub fn from_slot(slot: &[u8]) -> Self {
+        Self {
+            opcode: slot[0],
+            dst: slot[1] & 0x0f,
+            src: slot[1] >> 4,
+            off: i16::from_le_bytes([slot[2], slot[3]]),
+            imm: i32::from_le_bytes([slot[4], slot[5], slot[6], slot[7]]),
+        }
+    }
+
+    pub fn class(&self) -> OpcodeClass {
+        match self.opcode & 0x07 {
+            BPF_LD => OpcodeClass::Ld,
+            BPF_LDX => OpcodeClass::Ldx,
+            BPF_ST => OpcodeClass::St,
+            BPF_STX => OpcodeClass::Stx,
+            BPF_ALU32 => OpcodeClass::Alu32,
+            BPF_JMP => OpcodeClass::Jmp,
+            BPF_PQR => OpcodeClass::Pqr,
+            _ => OpcodeClass::Alu64,
+        }
+    }
+
+    fn source(&self) -> Operand {
+        if self.opcode & 0x08 != 0 {
+            Operand::Reg(self.src)
+        } else {
+            Operand::Imm(self.imm as i64)
+        }
+    }
+}
+
+impl DecodedInsn {
+    pub fn class(&self) -> OpcodeClass {
+        self.raw.class()
+    }
+
+    /// Number of slots the instruction occupies
+    pub fn slots(&self) -> usize {
+        match self.insn {
+            Insn::LoadImm64 { .. } => 2,
+            _ => 1,
+        }
+    }
+
+    pub fn next_pc(&self) -> usize {
+        self.pc + self.slots()
+    }
+
+    /// Destination of a jump; `None` for any other instruction
+    pub fn jump_target(&self) -> Option<usize> {
+        match self.insn {
+            Insn::Jump { off, .. } => {
+                let target = self.pc as i64 + 1 + off as i64;
+                usize::try_from(target).ok()
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(text: &'a [u8], version: SbpfVersion) -> Self {
+        Self {
+            text,
+            version,
+            pc: 0,
+        }
+    }
+
+    /// Number of 8-byte slots in the section
+    pub fn slot_count(&self) -> usize {
+        self.text.len() / INSN_SIZE
+    }
+
+    /// Decodes the instruction starting at slot `pc`
+    pub fn decode_at(&self, pc: usize) -> Option<DecodedInsn> {
+        let slot = self.slot(pc)?;
+        let raw = RawInsn::from_slot(slot);
+        let insn = match raw.opcode {
+            LD_DW_IMM if self.version == SbpfVersion::V1 => match self.slot(pc + 1) {
+                Some(next) if next[0] == 0 => {
+                    let high = u32::from_le_bytes([next[4], next[5], next[6], next[7]]);
+                    Insn::LoadImm64 {
+                        dst: raw.dst,
+                        imm: (high as u64) << 32 | raw.imm as u32 as u64,
+                    }
+                }
+                _ => Insn::Invalid { opcode: raw.opcode },
+            },
+            _ => self.decode_slot(&raw),
+        };
+        Some(DecodedInsn { pc, raw, insn })
+    }
+
+    fn slot(&self, pc: usize) -> Option<&'a [u8]> {
+        let start = pc.checked_mul(INSN_SIZE)?;
+        self.text.get(start..start.checked_add(INSN_SIZE)?)
+    }
+
+    fn decode_slot(&self, raw: &RawInsn) -> Insn {
+        let v2 = self.version == SbpfVersion::V2;
+        let opcode = raw.opcode;
+        let invalid = Insn::Invalid { opcode };
+
+        match raw.class() {
+            OpcodeClass::Ldx if opcode & 0xe0 == 0x60 => Insn::Load {
+                size: MemSize::from_opcode(opcode),
+                dst: raw.dst,
+                src: raw.src,
+                off: raw.off,
+            },
+            OpcodeClass::St | OpcodeClass::Stx if opcode & 0xe0 == 0x60 => Insn::Store {
+                size: MemSize::from_opcode(opcode),
+                dst: raw.dst,
+                off: raw.off,
+                src: if raw.class() == OpcodeClass::Stx {
+                    Operand::Reg(raw.src)
+                } else {
+                    Operand::Imm(raw.imm as i64)
+                },
+            },
+            OpcodeClass::Alu32 | OpcodeClass::Alu64 => {
+                let is_64 = raw.class() == OpcodeClass::Alu64;
+                let op = match opcode & 0xf0 {
+                    0x00 => AluOp::Add,
+                    0x10 => AluOp::Sub,
+                    0x20 => AluOp::Mul,
+                    0x30 => AluOp::Div,
+                    0x40 => AluOp::Or,
+                    0x50 => AluOp::And,
+                    0x60 => AluOp::Lsh,
+                    0x70 => AluOp::Rsh,
+                    0x80 if !v2 && opcode & 0x08 == 0 => AluOp::Neg,
+                    0x90 => AluOp::Mod,
+                    0xa0 => AluOp::Xor,
+                    0xb0 => AluOp::Mov,
+                    0xc0 => AluOp::Arsh,
+                    // Only the 32-bit class encodes byte swaps; the source bit picks `be`
+                    0xd0 if !is_64 && opcode & 0x08 != 0 => AluOp::Be,
+                    0xd0 if !is_64 && !v2 => AluOp::Le,
+                    0xf0 if v2 && is_64 && opcode & 0x08 == 0 => AluOp::Hor,
+                    _ => return invalid,
+                };
+                let src = match op {
+                    AluOp::Le | AluOp::Be => Operand::Imm(raw.imm as i64),
+                    _ => raw.source(),
+                };
+                Insn::Alu { op, is_64, dst: raw.dst, src }
+            }
+            OpcodeClass::Pqr if v2 => {
+                let op = match opcode & 0xe0 {
+                    0x20 => AluOp::Uhmul,
+                    0x40 => AluOp::Udiv,
+                    0x60 => AluOp::Urem,
+                    0x80 => AluOp::Lmul,
+                    0xa0 => AluOp::Shmul,
+                    0xc0 => AluOp::Sdiv,
+                    0xe0 => AluOp::Srem,
+                    _ => return invalid,
+                };
+                Insn::Alu {
+                    op,
+                    is_64: opcode & 0x10 != 0,
+                    dst: raw.dst,
+                    src: raw.source(),
+                }
+            }
+            OpcodeClass::Jmp => {
+                let cond = match opcode & 0xf0 {
+                    0x00 if opcode == 0x05 => JumpCondition::Always,
+                    0x10 => JumpCondition::Eq,
+                    0x20 => JumpCondition::Gt,
+                    0x30 => JumpCondition::Ge,
+                    0x40 => JumpCondition::Set,
+                    0x50 => JumpCondition::Ne,
+                    0x60 => JumpCondition::Sgt,
+                    0x70 => JumpCondition::Sge,
+                    0xa0 => JumpCondition::Lt,
+                    0xb0 => JumpCondition::Le,
+                    0xc0 => JumpCondition::Slt,
+                    0xd0 => JumpCondition::Sle,
+                    _ => {
+                        return match opcode {
+                            CALL_IMM => Insn::Call {
+                                src: raw.src,
+                                imm: raw.imm,
+                            },
+                            // SBFv1 keeps the callx target register in the immediate
+                            CALL_REG => Insn::CallReg {
+                                reg: if v2 { raw.src } else { raw.imm as u8 },
+                            },
+                            EXIT => Insn::Exit,
+                            _ => invalid,
+                        }
+                    }
+                };
+                Insn::Jump {
+                    cond,
+                    dst: raw.dst,
+                    src: raw.source(),
+                    off: raw.off,
+                }
+            }
+            _ => invalid,
+        }
+    }
+}
+
+impl<'a> Iterator for Decoder<'a> {
+    type Item = DecodedInsn;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let decoded = self.decode_at(self.pc)?;
+        self.pc = decoded.next_pc();
+        Some(decoded)
+    }
+}
+
+impl AluOp {
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            AluOp::Add => "add",
+            AluOp::Sub => "sub",
+            AluOp::Mul => "mul",
+            AluOp::Div => "div",
+            AluOp::Or => "or",
+            AluOp::And => "and",
+            AluOp::Lsh => "lsh",
+            AluOp::Rsh => "rsh",
+            AluOp::Neg => "neg",
+            AluOp::Mod => "mod",
+            AluOp::Xor => "xor",
+            AluOp::Mov => "mov",
+            AluOp::Arsh => "arsh",
+            AluOp::Le => "le",
+            AluOp::Be => "be",
+            AluOp::Hor => "hor",
+            AluOp::Uhmul => "uhmul",
+            AluOp::Udiv => "udiv",
+            AluOp::Urem => "urem",
+            AluOp::Lmul => "lmul",
+            AluOp::Shmul => "shmul",
+            AluOp::Sdiv => "sdiv",
+            AluOp::Srem => "srem",
+        }
+    }
+}
+
+impl JumpCondition {
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            JumpCondition::Always => "ja",
+            JumpCondition::Eq => "jeq",
+            JumpCondition::Gt => "jgt",
+            JumpCondition::Ge => "jge",
+            JumpCondition::Set => "jset",
+            JumpCondition::Ne => "jne",
+            JumpCondition::Sgt => "jsgt",
+            JumpCondition::Sge => "jsge",
+            JumpCondition::Lt => "jlt",
+            JumpCondition::Le => "jle",
+            JumpCondition::Slt => "jslt",
+            JumpCondition::Sle => "jsle",
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Reg(reg) => write!(f, "r{}", reg),
+            Operand::Imm(imm) => write!(f, "{}", imm),
+        }
+    }
+}
+
+/// Formats a memory operand as `[rN+0x10]`
+struct Address(u8, i16);
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.1 < 0 { '-' } else { '+' };
+        write!(f, "[r{}{}{:#x}]", self.0, sign, self.1.unsigned_abs())
+    }
+}
+
+impl fmt::Display for Insn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Insn::LoadImm64 { dst, imm } => write!(f, "lddw r{}, {:#x}", dst, imm),
+            Insn::Load { size, dst, src, off } => {
+                write!(f, "ldx{} r{}, {}", size.suffix(), dst, Address(src, off))
+            }
+            Insn::Store { size, dst, off, src } => {
+                let prefix = if matches!(src, Operand::Reg(_)) { "stx" } else { "st" };
+                write!(f, "{}{} {}, {}", prefix, size.suffix(), Address(dst, off), src)
+            }
+            Insn::Alu { op: op @ (AluOp::Le | AluOp::Be), dst, src, .. } => {
+                write!(f, "{}{} r{}", op.mnemonic(), src, dst)
+            }
+            Insn::Alu { op: AluOp::Neg, is_64, dst, .. } => {
+                write!(f, "neg{} r{}", if is_64 { 64 } else { 32 }, dst)
+            }
+            Insn::Alu { op, is_64, dst, src } => {
+                let width = if is_64 { 64 } else { 32 };
+                write!(f, "{}{} r{}, {}", op.mnemonic(), width, dst, src)
+            }
+            Insn::Jump { cond: JumpCondition::Always, off, .. } => write!(f, "ja {:+}", off),
+            Insn::Jump { cond, dst, src, off } => {
+                write!(f, "{} r{}, {}, {:+}", cond.mnemonic(), dst, src, off)
+            }
+            Insn::Call { src: 1, imm } => write!(f, "call {:+}", imm),
+            Insn::Call { imm, .. } => write!(f, "call {:#x}", imm as u32),
+            Insn::CallReg { reg } => write!(f, "callx r{}", reg),
+            Insn::Exit => write!(f, "exit"),
+            Insn::Invalid { opcode } => write!(f, "invalid {:#04x}", opcode),
+        }
+    }
+}
+
+/// Renders a listing of `text`, one `pc: bytes  mnemonic` line per instruction
+pub fn disassemble(text: &[u8], version: SbpfVersion) -> String {
+    use std::fmt::Write;
+
+    let mut listing = String::new();
+    for decoded in Decoder::new(text, version) {
+        let start = decoded.pc * INSN_SIZE;
+        let _ = write!(listing, "{:>6}:", decoded.pc);
+        for byte in &text[start..start + INSN_SIZE] {
+            let _ = write!(listing, " {:02x}", byte);
+        }
+        let _ = writeln!(listing, "  {}", decoded.insn);
+    }
+    listing
+}
+
+/// Encodes instructions for tests
+#[cfg(test)]
+pub(crate) mod test_utils {
+    /// One instruction slot; `lddw` takes a second one holding the upper half
+    /// of its immediate
+    pub fn slot(opcode: u8, dst: u8, src: u8, off: i16, imm: i32) -> Vec<u8> {
+        let mut bytes = vec![opcode, src << 4 | dst];
+        bytes.extend_from_slice(&off.to_le_bytes());
+        bytes.extend_from_slice(&imm.to_le_bytes());
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_utils::slot;
+    use super::*;
+
+    #[test]
+    fn test_decode_instruction_classes() {
+        let text = [
+            slot(0x79, 1, 2, 8, 0),    // ldxdw r1, [r2+0x8]
+            slot(0x7b, 10, 1, -8, 0),  // stxdw [r10-0x8], r1
+            slot(0x62, 10, 0, -4, 7),  // stw [r10-0x4], 7
+            slot(0x07, 1, 0, 0, 5),    // add64 r1, 5
+            slot(0xbc, 2, 3, 0, 0),    // mov32 r2, r3
+            slot(0x15, 1, 0, 2, 0),    // jeq r1, 0, +2
+            slot(0x85, 0, 0, 0, -1),   // call 0xffffffff
+            slot(0x95, 0, 0, 0, 0),    // exit
+        ]
+        .concat();
+        let decoded: Vec<_> = Decoder::new(&text, SbpfVersion::V1).collect();
+
+        assert_eq!(decoded.len(), 8);
+        assert_eq!(decoded[0].class(), OpcodeClass::Ldx);
+        assert_eq!(
+            decoded[0].insn,
+            Insn::Load { size: MemSize::DoubleWord, dst: 1, src: 2, off: 8 }
+        );
+        assert_eq!(
+            decoded[1].insn,
+            Insn::Store { size: MemSize::DoubleWord, dst: 10, off: -8, src: Operand::Reg(1) }
+        );
+        assert_eq!(
+            decoded[2].insn,
+            Insn::Store { size: MemSize::Word, dst: 10, off: -4, src: Operand::Imm(7) }
+        );
+        assert_eq!(
+            decoded[3].insn,
+            Insn::Alu { op: AluOp::Add, is_64: true, dst: 1, src: Operand::Imm(5) }
+        );
+        assert_eq!(
+            decoded[4].insn,
+            Insn::Alu { op: AluOp::Mov, is_64: false, dst: 2, src: Operand::Reg(3) }
+        );
+        assert_eq!(decoded[5].jump_target(), Some(8));
+        assert_eq!(decoded[6].insn, Insn::Call { src: 0, imm: -1 });
+        assert_eq!(decoded[7].insn, Insn::Exit);
+    }
+
+    #[test]
+    fn test_decode_lddw() {
+        let mut text = slot(LD_DW_IMM, 3, 0, 0, 0x5566_7788);
+        text.extend(slot(0, 0, 0, 0, 0x1122_3344));
+        text.extend(slot(EXIT, 0, 0, 0, 0));
+
+        let decoded: Vec<_> = Decoder::new(&text, SbpfVersion::V1).collect();
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[0].insn, Insn::LoadImm64 { dst: 3, imm: 0x1122_3344_5566_7788 });
+        assert_eq!(decoded[0].slots(), 2);
+        assert_eq!(decoded[1].pc, 2);
+
+        // A truncated lddw cannot be decoded
+        let truncated = slot(LD_DW_IMM, 3, 0, 0, 1);
+        let decoded = Decoder::new(&truncated, SbpfVersion::V1).next().unwrap();
+        assert_eq!(decoded.insn, Insn::Invalid { opcode: LD_DW_IMM });
+    }
+
+    #[test]
+    fn test_decode_version_differences() {
+        let callx = slot(CALL_REG, 0, 4, 0, 2);
+        let v1 = Decoder::new(&callx, SbpfVersion::V1).next().unwrap();
+        let v2 = Decoder::new(&callx, SbpfVersion::V2).next().unwrap();
+        assert_eq!(v1.insn, Insn::CallReg { reg: 2 });
+        assert_eq!(v2.insn, Insn::CallReg { reg: 4 });
+
+        // lddw and neg are gone in v2, hor64 and the PQR class are new
+        let lddw = [slot(LD_DW_IMM, 1, 0, 0, 1), slot(0, 0, 0, 0, 0)].concat();
+        assert!(matches!(
+            Decoder::new(&lddw, SbpfVersion::V2).next().unwrap().insn,
+            Insn::Invalid { .. }
+        ));
+        let neg = slot(0x87, 1, 0, 0, 0);
+        assert!(matches!(
+            Decoder::new(&neg, SbpfVersion::V2).next().unwrap().insn,
+            Insn::Invalid { .. }
+        ));
+        let hor = slot(0xf7, 1, 0, 0, 9);
+        assert_eq!(
+            Decoder::new(&hor, SbpfVersion::V2).next().unwrap().insn,
+            Insn::Alu { op: AluOp::Hor, is_64: true, dst: 1, src: Operand::Imm(9) }
+        );
+        let udiv64 = slot(0x5e, 1, 2, 0, 0);
+        assert_eq!(
+            Decoder::new(&udiv64, SbpfVersion::V2).next().unwrap().insn,
+            Insn::Alu { op: AluOp::Udiv, is_64: true, dst: 1, src: Operand::Reg(2) }
+        );
+        assert!(matches!(
+            Decoder::new(&udiv64, SbpfVersion::V1).next().unwrap().insn,
+            Insn::Invalid { .. }
+        ));
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let mut text = slot(LD_DW_IMM, 1, 0, 0, 0x10);
+        text.extend(slot(0, 0, 0, 0, 0));
+        text.extend(slot(0x71, 2, 1, -1, 0));
+        text.extend(slot(0x05, 0, 0, -1, 0));
+        text.extend(slot(EXIT, 0, 0, 0, 0));
+
+        let listing = disassemble(&text, SbpfVersion::V1);
+        let lines: Vec<_> = listing.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], "     0: 18 01 00 00 10 00 00 00  lddw r1, 0x10");
+        assert!(lines[1].ends_with("ldxb r2, [r1-0x1]"));
+        assert!(lines[2].ends_with("ja -1"));
+        assert!(lines[3].starts_with("     4:"));
+        assert!(lines[3].ends_with("exit"));
+    }
+}
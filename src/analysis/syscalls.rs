@@ -0,0 +1,258 @@
+// program/src/analysis/syscalls.rs
+//! Syscalls a program calls and the capabilities they reveal
+//!
+//! Syscalls are identified by the murmur3 hash of their name: dynamic
+//! binaries name them through relocations, static ones embed the hash in the
+//! `call` immediate.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use super::{
+    cfg::{CallTarget, Cfg},
+    sbpf::Insn,
+};
+
+/// Syscalls registered by the runtime
+pub const SYSCALLS: &[&str] = &[
+    "abort",
+    "sol_panic_",
+    "sol_log_",
+    "sol_log_64_",
+    "sol_log_compute_units_",
+    "sol_log_pubkey",
+    "sol_log_data",
+    "sol_create_program_address",
+    "sol_try_find_program_address",
+    "sol_sha256",
+    "sol_keccak256",
+    "sol_blake3",
+    "sol_secp256k1_recover",
+    "sol_poseidon",
+    "sol_curve_validate_point",
+    "sol_curve_group_op",
+    "sol_curve_multiscalar_mul",
+    "sol_alt_bn128_group_op",
+    "sol_alt_bn128_compression",
+    "sol_big_mod_exp",
+    "sol_get_clock_sysvar",
+    "sol_get_epoch_schedule_sysvar",
+    "sol_get_fees_sysvar",
+    "sol_get_rent_sysvar",
+    "sol_get_last_restart_slot",
+    "sol_get_epoch_rewards_sysvar",
+    "sol_get_sysvar",
+    "sol_get_epoch_stake",
+    "sol_memcpy_",
+    "sol_memmove_",
+    "sol_memcmp_",
+    "sol_memset_",
+    "sol_invoke_signed_c",
+    "sol_invoke_signed_rust",
+    "sol_set_return_data",
+    "sol_get_return_data",
+    "sol_get_stack_height",
+    "sol_get_processed_sibling_instruction",
+    "sol_remaining_compute_units",
+    "sol_alloc_free_",
+];
+
+/// `SYSCALLS` keyed by their hash, sorted for binary search
+const SYSCALLS_BY_HASH: [(u32, &str); SYSCALLS.len()] = sort_by_hash(SYSCALLS);
+
+/// Number of call sites of one syscall
+#[derive(BorshSerialize, BorshDeserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SyscallCount {
+    /// Murmur3 hash of the syscall name
+    pub hash: u32,
+    pub call_sites: u16,
+}
+
+impl SyscallCount {
+    /// Name of the syscall, if it is a known one
+    pub fn name(&self) -> Option<&'static str> {
+        resolve(self.hash)
+    }
+}
+
+/// Groups of syscalls, as a bitset
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Capabilities(u8);
+
+impl Capabilities {
+    pub const LOGGING: Self = Self(1 << 0);
+    pub const CPI: Self = Self(1 << 1);
+    pub const RETURN_DATA: Self = Self(1 << 2);
+    pub const PDA: Self = Self(1 << 3);
+    pub const CRYPTO: Self = Self(1 << 4);
+    pub const SYSVARS: Self = Self(1 << 5);
+
+    /// Capability granted by the syscall named `name`
+    pub fn of(name: &str) -> Self {
+        match name {
+            "sol_log_" | "sol_log_64_" | "sol_log_compute_units_" | "sol_log_pubkey" | "sol_log_data" => {
+                Self::LOGGING
+            }
+            "sol_invoke_signed_c" | "sol_invoke_signed_rust" | "sol_get_processed_sibling_instruction"
+            | "sol_get_stack_height" => Self::CPI,
+            "sol_set_return_data" | "sol_get_return_data" => Self::RETURN_DATA,
+            "sol_create_program_address" | "sol_try_find_program_address" => Self::PDA,
+            "sol_sha256" | "sol_keccak256" | "sol_blake3" | "sol_secp256k1_recover" | "sol_poseidon"
+            | "sol_curve_validate_point" | "sol_curve_group_op" | "sol_curve_multiscalar_mul"
+            | "sol_alt_bn128_group_op" | "sol_alt_bn128_compression" | "sol_big_mod_exp" => {
+                Self::CRYPTO
+            }
+            "sol_get_clock_sysvar" | "sol_get_epoch_schedule_sysvar" | "sol_get_fees_sysvar"
+            | "sol_get_rent_sysvar" | "sol_get_last_restart_slot" | "sol_get_epoch_rewards_sysvar"
+            | "sol_get_sysvar" | "sol_get_epoch_stake" => Self::SYSVARS,
+            _ => Self::default(),
+        }
+    }
+
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn insert(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+}
+
+/// Hash identifying a syscall, as used by the runtime's function registry
+pub const fn syscall_hash(name: &str) -> u32 {
+    murmur3_32(name.as_bytes(), 0)
+}
+
+/// Name of the known syscall with the given hash
+pub fn resolve(hash: u32) -> Option<&'static str> {
+    let index = SYSCALLS_BY_HASH.binary_search_by_key(&hash, |&(hash, _)| hash).ok()?;
+    Some(SYSCALLS_BY_HASH[index].1)
+}
+
+/// Pairs each of `names` with its hash, sorted by hash
+const fn sort_by_hash<const N: usize>(names: &[&'static str]) -> [(u32, &'static str); N] {
+    let mut table = [(0, ""); N];
+    let mut sorted = 0;
+    // Insertion sort, as iterators and `sort` are not available in const fns
+    while sorted < N {
+        let entry = (syscall_hash(names[sorted]), names[sorted]);
+        let mut index = sorted;
+        while index > 0 && table[index - 1].0 >= entry.0 {
+            assert!(table[index - 1].0 != entry.0, "syscall hashes collide");
+            table[index] = table[index - 1];
+            index -= 1;
+        }
+        table[index] = entry;
+        sorted += 1;
+    }
+    table
+}
+
+/// Counts the call sites of each syscall in `.text`, in order of first use
+pub fn inventory(cfg: &Cfg) -> Vec<SyscallCount> {
+    let mut counts: Vec<SyscallCount> = Vec::new();
+    for decoded in cfg.decoder() {
+        if !matches!(decoded.insn, Insn::Call { .. }) {
+            continue;
+        }
+        let hash = match cfg.call_target(&decoded) {
+            Some(CallTarget::External(name)) => syscall_hash(name),
+            Some(CallTarget::Hashed(hash)) => hash,
+            _ => continue,
+        };
+        match counts.iter_mut().find(|count| count.hash == hash) {
+            Some(count) => count.call_sites = count.call_sites.saturating_add(1),
+            None => counts.push(SyscallCount { hash, call_sites: 1 }),
+        }
+    }
+    counts
+}
+
+/// 32-bit MurmurHash3 (x86 variant), usable in constants
+pub const fn murmur3_32(bytes: &[u8], seed: u32) -> u32 {
+    const fn scramble(k: u32) -> u32 {
+        k.wrapping_mul(0xcc9e_2d51).rotate_left(15).wrapping_mul(0x1b87_3593)
+    }
+
+    let mut hash = seed;
+    let mut offset = 0;
+    while offset + 4 <= bytes.len() {
+        let k = u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]]);
+        hash ^= scramble(k);
+        hash = hash.rotate_left(13).wrapping_mul(5).wrapping_add(0xe654_6b64);
+        offset += 4;
+    }
+    if offset < bytes.len() {
+        let mut k = 0u32;
+        let mut index = bytes.len();
+        while index > offset {
+            index -= 1;
+            k = (k << 8) | bytes[index] as u32;
+        }
+        hash ^= scramble(k);
+    }
+
+    hash ^= bytes.len() as u32;
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x85eb_ca6b);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(0xc2b2_ae35);
+    hash ^ (hash >> 16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{cfg::ExternalCall, sbpf::SbpfVersion};
+    use super::*;
+
+    #[test]
+    fn test_syscall_hash() {
+        assert_eq!(syscall_hash("abort"), 0xb6fc_1a11);
+        assert_eq!(syscall_hash("sol_log_"), 0x2075_59bd);
+        assert_eq!(syscall_hash("sol_invoke_signed_rust"), 0xd744_9092);
+        assert_eq!(resolve(0x2075_59bd), Some("sol_log_"));
+        assert_eq!(resolve(0), None);
+        // Tails of one to three bytes
+        assert_eq!(murmur3_32(b"a", 0), 0x3c25_69b2);
+        assert_eq!(murmur3_32(b"abc", 0), 0xb3dd_93fa);
+    }
+
+    #[test]
+    fn test_resolve_every_syscall() {
+        assert!(SYSCALLS_BY_HASH.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        for &name in SYSCALLS {
+            assert_eq!(resolve(syscall_hash(name)), Some(name));
+        }
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let mut capabilities = Capabilities::default();
+        capabilities.insert(Capabilities::of("sol_log_data"));
+        capabilities.insert(Capabilities::of("sol_get_clock_sysvar"));
+        assert!(capabilities.contains(Capabilities::LOGGING));
+        assert!(capabilities.contains(Capabilities::SYSVARS));
+        assert!(!capabilities.contains(Capabilities::CPI));
+        assert_eq!(Capabilities::of("sol_memcpy_"), Capabilities::default());
+    }
+
+    #[test]
+    fn test_inventory() {
+        let relocated = [0x85, 0x10, 0, 0, 0xff, 0xff, 0xff, 0xff];
+        let hashed = [0x85, 0, 0, 0, 0xbd, 0x59, 0x75, 0x20]; // call sol_log_
+        let internal = [0x85, 0x10, 0, 0, 0, 0, 0, 0];
+        let exit = [0x95, 0, 0, 0, 0, 0, 0, 0];
+        let text = [relocated, hashed, relocated, internal, exit].concat();
+        let external_calls = vec![
+            ExternalCall { pc: 0, name: "sol_log_" },
+            ExternalCall { pc: 2, name: "sol_memcpy_" },
+        ];
+        let cfg = Cfg::new(&text, SbpfVersion::V1, 0, [], external_calls);
+        assert_eq!(
+            inventory(&cfg),
+            vec![
+                SyscallCount { hash: syscall_hash("sol_log_"), call_sites: 2 },
+                SyscallCount { hash: syscall_hash("sol_memcpy_"), call_sites: 1 },
+            ]
+        );
+    }
+}
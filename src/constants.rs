@@ -1,13 +1,48 @@
+use solana_program::{pubkey, pubkey::Pubkey};
+
 pub const MAX_CONTRACT_SIZE: usize = 1024 * 1024; // 1 MB
 pub const MAX_ANALYSIS_BUFFER: usize = 1024;
 pub const MAX_METRICS_BUFFER: usize = 512;
+pub const MAX_STORED_FINDINGS: usize = 16;
+pub const MAX_STORED_SYSCALLS: usize = 32;
+pub const MAX_STORED_PDA_DERIVATIONS: usize = 8;
+pub const DEFAULT_ANALYSIS_HISTORY: usize = 10; // history capacity of a `SecurityAnalysisState::SPACE` account
+pub const DEFAULT_GAS_HISTORY: usize = 100; // history capacity of a `MetricsState::SPACE` account
+
+// PDA seeds
+pub const PROGRAM_STATE_SEED: &[u8] = b"program_state";
+pub const METRICS_SEED: &[u8] = b"metrics"; // followed by the target program id
 
 // Version information
 pub const PROGRAM_VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const ACCOUNT_LAYOUT_VERSION: u8 = 2; // layout of the state accounts: v1 had no header
 
 // Analysis thresholds
 pub const GAS_WARNING_THRESHOLD: u64 = 100_000;
-pub const RISK_SCORE_THRESHOLD: u8 = 80;
+pub const RISK_SCORE_THRESHOLD: u8 = 80; // minimum safety score to pass under the default risk model
+pub const MAX_DETECTORS: usize = 32;
+pub const DEFAULT_COMPUTE_BUDGET: u32 = 200_000; // default compute unit limit of an instruction
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000; // highest limit a transaction can request
+
+// Panic messages rustc emits for overflowing arithmetic when overflow-checks is enabled
+pub const OVERFLOW_PANIC_MESSAGES: &[&[u8]] = &[
+    b"attempt to add with overflow",
+    b"attempt to subtract with overflow",
+    b"attempt to multiply with overflow",
+];
+
+// Multisig programs whose signer PDAs are trusted as upgrade authorities
+pub const SQUADS_V3_PROGRAM_ID: Pubkey = pubkey!("SMPLecH534NA9acpos4G6x7uf3LWbCAwZQE9e8ZekMu");
+pub const SQUADS_V4_PROGRAM_ID: Pubkey = pubkey!("SQDS4ep65T869zMMBKyuUq6aD6EgTu8psMjkvj52pCf");
+pub const SERUM_MULTISIG_PROGRAM_ID: Pubkey = pubkey!("msigmtwzgXJHj2ext4XJjCDmpbcMuufFb5cHuwg6Xdt");
+pub const GOKI_SMART_WALLET_PROGRAM_ID: Pubkey = pubkey!("GokivDYuQXPZCWRkwMhdH2h91KpDQXBEmpgBgs55bnpH");
+pub const KNOWN_MULTISIG_PROGRAMS: &[Pubkey] = &[
+    SQUADS_V3_PROGRAM_ID,
+    SQUADS_V4_PROGRAM_ID,
+    SERUM_MULTISIG_PROGRAM_ID,
+    GOKI_SMART_WALLET_PROGRAM_ID,
+];
+pub const MULTISIG_SIGNER_INDICES: u8 = 4; // vault or derived wallet indices searched per multisig
 
 // Network constants
 pub const DEFAULT_TRANSACTION_SIZE: usize = 1232;
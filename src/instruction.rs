@@ -1,25 +1,34 @@
 // program/src/instruction.rs
 use borsh::{BorshDeserialize, BorshSerialize};
-use solana_program::program_error::ProgramError;
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+
+use crate::state::{AccountType, RiskModel};
 
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
 pub enum GuardInstruction {
     /// Analyzes a contract for common vulnerabilities and patterns
     /// 
     /// Accounts expected:
-    /// 0. `[readable]` Target program account to analyze
+    /// 0. `[readable]` Target program account to analyze, deployed by one of
+    ///    the BPF loaders
     /// 1. `[writable]` Analysis result storage account
     /// 2. `[signer]` Analysis requester
+    /// 3. `[]` Program state account
+    /// 4. `[readable]` Target's ProgramData account, required when the target
+    ///    is owned by the BPF Upgradeable Loader
+    /// 5. `[readable]` Optional multisig account whose vault or signer PDA is
+    ///    the upgrade authority, used to recognize multisig-controlled programs
     AnalyzeContract {
         /// Buffer size for analysis data
         data_size: u64,
     },
 
-    /// Records gas usage patterns and transaction statistics
+    /// Records gas usage patterns and transaction statistics for a program
     /// 
     /// Accounts expected:
-    /// 0. `[writable]` Metrics storage account
+    /// 0. `[writable]` Metrics account, PDA of `["metrics", target program]`
     /// 1. `[signer]` Transaction submitter
+    /// 2. `[]` Target program the transaction invoked
     RecordMetrics {
         /// Gas used in the transaction
         gas_used: u64,
@@ -30,14 +39,118 @@ pub enum GuardInstruction {
     /// Updates network health indicators
     /// 
     /// Accounts expected:
-    /// 0. `[writable]` Network stats account
-    /// 1. `[signer]` Update authority
+    /// 0. `[writable]` Program state account holding the network stats
+    /// 1. `[signer]` Program state authority
     UpdateNetworkStats {
         /// Current TPS
         transactions_per_second: u64,
         /// Average block time
         average_block_time: u64,
     },
+
+    /// Creates the program state account at its canonical PDA
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Program state account, PDA of `["program_state"]`
+    /// 1. `[writable, signer]` Payer funding the rent-exempt balance
+    /// 2. `[]` System program
+    Initialize {
+        /// The account allowed to update program settings
+        authority: Pubkey,
+    },
+
+    /// Nominates a new authority, which takes over once it accepts
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Program state account
+    /// 1. `[signer]` Current program state authority
+    ProposeAuthority {
+        /// The account nominated as the next authority
+        new_authority: Pubkey,
+    },
+
+    /// Completes a handover started by `ProposeAuthority`
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Program state account
+    /// 1. `[signer]` Pending authority
+    AcceptAuthority,
+
+    /// Permanently removes the authority, freezing all program settings
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Program state account
+    /// 1. `[signer]` Current program state authority
+    RenounceAuthority,
+
+    /// Replaces the model used to score analysis findings
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Program state account
+    /// 1. `[signer]` Program state authority
+    UpdateRiskModel {
+        /// Weights, multipliers and bounds of the new model
+        risk_model: RiskModel,
+    },
+
+    /// Rewrites an account created under an older layout in the current one,
+    /// growing it and topping up its rent-exempt balance as needed. Metrics
+    /// accounts before v2 move with `MigrateMetrics` instead.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Program state or analysis account
+    /// 1. `[writable, signer]` Payer funding the extra rent
+    /// 2. `[]` System program
+    MigrateAccount {
+        /// Type of state the account holds
+        account_type: AccountType,
+    },
+
+    /// Changes how many entries the history of an analysis or metrics
+    /// account holds, keeping the most recent ones. The account is resized
+    /// to fit and its rent-exempt balance topped up by the authority when
+    /// growing, or refunded to the recipient when shrinking.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Analysis or metrics account
+    /// 1. `[writable, signer]` Program state authority, paying for growth
+    /// 2. `[]` Program state account
+    /// 3. `[]` System program
+    /// 4. `[writable]` Recipient of the rent freed by shrinking, e.g. whoever
+    ///    paid for the account
+    ResizeHistory {
+        /// Number of entries the history will hold
+        capacity: u32,
+    },
+
+    /// Creates the metrics account of a program at its PDA
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Metrics account, PDA of `["metrics", target program]`
+    /// 1. `[writable, signer]` Payer funding the rent-exempt balance
+    /// 2. `[]` Target program the metrics are recorded for
+    /// 3. `[]` System program
+    InitializeMetrics {
+        /// Number of gas samples the history holds
+        history_capacity: u32,
+    },
+
+    /// Moves a metrics account of a layout before v2 to the PDA of the
+    /// program it measured, keeping the most recent samples. The old account
+    /// is closed and its lamports fund the new one, the payer covering any
+    /// shortfall.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` Metrics account in a layout before v2
+    /// 1. `[writable]` Metrics account, PDA of `["metrics", target program]`
+    /// 2. `[writable, signer]` Program state authority, paying any shortfall
+    /// 3. `[]` Program state account
+    /// 4. `[]` Target program the old metrics were recorded for
+    /// 5. `[]` System program
+    MigrateMetrics {
+        /// Number of gas samples the new history holds
+        history_capacity: u32,
+    },
 }
 
 impl GuardInstruction {
@@ -62,6 +175,38 @@ impl GuardInstruction {
                     average_block_time,
                 }
             }
+            3 => {
+                let (authority, _) = Self::unpack_pubkey(rest)?;
+                Self::Initialize { authority }
+            }
+            4 => {
+                let (new_authority, _) = Self::unpack_pubkey(rest)?;
+                Self::ProposeAuthority { new_authority }
+            }
+            5 => Self::AcceptAuthority,
+            6 => Self::RenounceAuthority,
+            7 => {
+                let risk_model = RiskModel::deserialize(&mut &rest[..])
+                    .map_err(|_| ProgramError::InvalidInstructionData)?;
+                Self::UpdateRiskModel { risk_model }
+            }
+            8 => {
+                let account_type = AccountType::deserialize(&mut &rest[..])
+                    .map_err(|_| ProgramError::InvalidInstructionData)?;
+                Self::MigrateAccount { account_type }
+            }
+            9 => {
+                let (capacity, _) = Self::unpack_u32(rest)?;
+                Self::ResizeHistory { capacity }
+            }
+            10 => {
+                let (history_capacity, _) = Self::unpack_u32(rest)?;
+                Self::InitializeMetrics { history_capacity }
+            }
+            11 => {
+                let (history_capacity, _) = Self::unpack_u32(rest)?;
+                Self::MigrateMetrics { history_capacity }
+            }
             _ => return Err(ProgramError::InvalidInstructionData),
         })
     }
@@ -75,6 +220,26 @@ impl GuardInstruction {
         let value = u64::from_le_bytes(bytes.try_into().unwrap());
         Ok((value, rest))
     }
+
+    /// Unpacks a u32 from a byte buffer
+    fn unpack_u32(input: &[u8]) -> Result<(u32, &[u8]), ProgramError> {
+        if input.len() < 4 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let (bytes, rest) = input.split_at(4);
+        let value = u32::from_le_bytes(bytes.try_into().unwrap());
+        Ok((value, rest))
+    }
+
+    /// Unpacks a Pubkey from a byte buffer
+    fn unpack_pubkey(input: &[u8]) -> Result<(Pubkey, &[u8]), ProgramError> {
+        if input.len() < 32 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let (bytes, rest) = input.split_at(32);
+        let value = Pubkey::new_from_array(bytes.try_into().unwrap());
+        Ok((value, rest))
+    }
 }
 
 #[cfg(test)]
@@ -99,4 +264,81 @@ mod test {
         let unpacked = GuardInstruction::unpack(&packed).unwrap();
         assert_eq!(instruction, unpacked);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_initialize_packing() {
+        let instruction = GuardInstruction::Initialize {
+            authority: Pubkey::new_unique(),
+        };
+        let packed = borsh::to_vec(&instruction).unwrap();
+        let unpacked = GuardInstruction::unpack(&packed).unwrap();
+        assert_eq!(instruction, unpacked);
+    }
+
+    #[test]
+    fn test_authority_rotation_packing() {
+        let instructions = [
+            GuardInstruction::ProposeAuthority {
+                new_authority: Pubkey::new_unique(),
+            },
+            GuardInstruction::AcceptAuthority,
+            GuardInstruction::RenounceAuthority,
+        ];
+        for instruction in instructions {
+            let packed = borsh::to_vec(&instruction).unwrap();
+            let unpacked = GuardInstruction::unpack(&packed).unwrap();
+            assert_eq!(instruction, unpacked);
+        }
+    }
+
+    #[test]
+    fn test_update_risk_model_packing() {
+        let risk_model = RiskModel {
+            pass_threshold: 60,
+            ..RiskModel::default()
+        };
+        let instruction = GuardInstruction::UpdateRiskModel { risk_model };
+        let packed = borsh::to_vec(&instruction).unwrap();
+        let unpacked = GuardInstruction::unpack(&packed).unwrap();
+        assert_eq!(instruction, unpacked);
+
+        // Truncated models are rejected
+        assert!(GuardInstruction::unpack(&packed[..packed.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_migrate_account_packing() {
+        let instruction = GuardInstruction::MigrateAccount {
+            account_type: AccountType::Metrics,
+        };
+        let packed = borsh::to_vec(&instruction).unwrap();
+        assert_eq!(packed, vec![8, 2]);
+        let unpacked = GuardInstruction::unpack(&packed).unwrap();
+        assert_eq!(instruction, unpacked);
+
+        assert!(GuardInstruction::unpack(&[8]).is_err());
+        assert!(GuardInstruction::unpack(&[8, 3]).is_err());
+    }
+
+    #[test]
+    fn test_resize_history_packing() {
+        let instruction = GuardInstruction::ResizeHistory { capacity: 25 };
+        let packed = borsh::to_vec(&instruction).unwrap();
+        let unpacked = GuardInstruction::unpack(&packed).unwrap();
+        assert_eq!(instruction, unpacked);
+        assert!(GuardInstruction::unpack(&packed[..3]).is_err());
+    }
+
+    #[test]
+    fn test_metrics_packing() {
+        for instruction in [
+            GuardInstruction::InitializeMetrics { history_capacity: 50 },
+            GuardInstruction::MigrateMetrics { history_capacity: 50 },
+        ] {
+            let packed = borsh::to_vec(&instruction).unwrap();
+            let unpacked = GuardInstruction::unpack(&packed).unwrap();
+            assert_eq!(instruction, unpacked);
+            assert!(GuardInstruction::unpack(&packed[..3]).is_err());
+        }
+    }
+}
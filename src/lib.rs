@@ -8,9 +8,11 @@ use solana_program::{
     msg,
 };
 
+pub mod analysis;
 pub mod error;
 pub mod instruction;
 pub mod processor;
+pub mod ring_buffer;
 pub mod state;
 pub mod constants;
 
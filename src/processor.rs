@@ -1,20 +1,43 @@
 // program/src/processor.rs
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    bpf_loader, bpf_loader_deprecated, bpf_loader_upgradeable,
     entrypoint::ProgramResult,
     msg,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
     pubkey::Pubkey,
     clock::Clock,
+    rent::Rent,
+    system_instruction,
+    system_program,
     sysvar::Sysvar,
 };
 
-use borsh::{BorshDeserialize, BorshSerialize};
+use borsh::BorshSerialize;
 
 use crate::{
+    analysis::{
+        cfg::{BlockExit, Cfg, Reachability},
+        compute::{self, FunctionCost},
+        elf::Elf,
+        input::{
+            self, AccountAccess, AccountField, BumpSource, Invoke, LamportDrain, PdaCall, ProgramIdSource,
+        },
+        loader::{self, ProgramDataHeader, UpgradeAuthorityKind},
+        sbpf::{AluOp, Insn, Operand},
+        syscalls,
+    },
     instruction::GuardInstruction,
-    state::{ProgramState, SecurityAnalysisState, MetricsState},
+    state::{
+        AccountHeader, AccountType, DetectorId, Finding, HistoryAccount, PdaDerivation, ProgramState,
+        RiskModel, SecurityAnalysisState, MetricsState, Severity, VersionedAccount,
+    },
     error::GuardError,
-    constants::{MAX_CONTRACT_SIZE, GAS_WARNING_THRESHOLD},
+    constants::{
+        ACCOUNT_LAYOUT_VERSION, MAX_COMPUTE_UNIT_LIMIT, MAX_CONTRACT_SIZE, GAS_WARNING_THRESHOLD, OVERFLOW_PANIC_MESSAGES,
+        METRICS_SEED, PROGRAM_STATE_SEED,
+    },
 };
 
 pub struct Processor;
@@ -45,7 +68,92 @@ impl Processor {
                     average_block_time,
                 )
             }
+            GuardInstruction::Initialize { authority } => {
+                msg!("Instruction: Initialize");
+                Self::process_initialize(program_id, accounts, authority)
+            }
+            GuardInstruction::ProposeAuthority { new_authority } => {
+                msg!("Instruction: ProposeAuthority");
+                Self::process_propose_authority(program_id, accounts, new_authority)
+            }
+            GuardInstruction::AcceptAuthority => {
+                msg!("Instruction: AcceptAuthority");
+                Self::process_accept_authority(program_id, accounts)
+            }
+            GuardInstruction::RenounceAuthority => {
+                msg!("Instruction: RenounceAuthority");
+                Self::process_renounce_authority(program_id, accounts)
+            }
+            GuardInstruction::UpdateRiskModel { risk_model } => {
+                msg!("Instruction: UpdateRiskModel");
+                Self::process_update_risk_model(program_id, accounts, risk_model)
+            }
+            GuardInstruction::MigrateAccount { account_type } => {
+                msg!("Instruction: MigrateAccount");
+                Self::process_migrate_account(program_id, accounts, account_type)
+            }
+            GuardInstruction::ResizeHistory { capacity } => {
+                msg!("Instruction: ResizeHistory");
+                Self::process_resize_history(program_id, accounts, capacity)
+            }
+            GuardInstruction::InitializeMetrics { history_capacity } => {
+                msg!("Instruction: InitializeMetrics");
+                Self::process_initialize_metrics(program_id, accounts, history_capacity)
+            }
+            GuardInstruction::MigrateMetrics { history_capacity } => {
+                msg!("Instruction: MigrateMetrics");
+                Self::process_migrate_metrics(program_id, accounts, history_capacity)
+            }
+        }
+    }
+
+    fn process_initialize(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        authority: Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let program_state_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        // Validate accounts
+        if !payer_info.is_signer {
+            return Err(GuardError::UnauthorizedAccount.into());
+        }
+
+        if system_program_info.key != &system_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let (expected_address, bump) = ProgramState::find_address(program_id);
+        if program_state_info.key != &expected_address {
+            msg!("Program state account is not the canonical PDA");
+            return Err(GuardError::InvalidAccountData.into());
+        }
+
+        if program_state_info.owner == program_id || !program_state_info.data_is_empty() {
+            msg!("Program state already initialized");
+            return Err(GuardError::InitializationFailed.into());
         }
+
+        Self::create_pda_account(
+            program_id,
+            program_state_info,
+            payer_info,
+            system_program_info,
+            ProgramState::SPACE,
+            &[PROGRAM_STATE_SEED, &[bump]],
+        )?;
+
+        let clock = Clock::get()?;
+        let state = ProgramState::new(authority, bump, clock.unix_timestamp);
+        state.serialize(&mut &mut program_state_info.try_borrow_mut_data()?[..])?;
+
+        msg!("Program state initialized. Authority: {}", authority);
+
+        Ok(())
     }
 
     fn process_analyze_contract(
@@ -59,56 +167,241 @@ impl Processor {
         let analysis_state_info = next_account_info(account_info_iter)?;
         let authority_info = next_account_info(account_info_iter)?;
         let program_state_info = next_account_info(account_info_iter)?;
+        let programdata_info = next_account_info(account_info_iter).ok();
+        let multisig_info = next_account_info(account_info_iter).ok();
 
         // Validate accounts
         if !authority_info.is_signer {
             return Err(GuardError::UnauthorizedAccount.into());
         }
 
-        if analysis_state_info.owner != program_id || program_state_info.owner != program_id {
+        if analysis_state_info.owner != program_id {
             return Err(GuardError::InvalidAccountData.into());
         }
+        let risk_model = Self::load_program_state(program_id, program_state_info)?.risk_model;
 
         // Get current timestamp
         let clock = Clock::get()?;
         let current_timestamp = clock.unix_timestamp;
 
         // Initialize or load analysis state
-        let mut analysis_state = if analysis_state_info.data_len() > 0 {
-            SecurityAnalysisState::try_from_slice(&analysis_state_info.data.borrow())?
-        } else {
+        let fresh_analysis_state = AccountHeader::is_uninitialized(&analysis_state_info.data.borrow());
+        let mut analysis_state = if fresh_analysis_state {
             SecurityAnalysisState::new(*target_program_info.key, current_timestamp)
+        } else {
+            SecurityAnalysisState::load(&analysis_state_info.data.borrow())?
+        };
+        if &analysis_state.target_program != target_program_info.key {
+            msg!("Analysis account belongs to another program");
+            return Err(GuardError::InvalidAccountData.into());
+        }
+
+        // Upgradeable programs keep their code in a separate ProgramData account
+        let target_data = target_program_info.try_borrow_data()?;
+        let programdata;
+        let mut upgrade_authority_kind = UpgradeAuthorityKind::Immutable;
+        let program_data: &[u8] = if target_program_info.owner == &bpf_loader_upgradeable::id() {
+            let programdata_info = Self::resolve_programdata(&target_data, programdata_info)?;
+            programdata = programdata_info.try_borrow_data()?;
+            let header = ProgramDataHeader::parse(&programdata).ok_or_else(|| {
+                msg!("Malformed ProgramData account");
+                GuardError::InvalidTargetProgram
+            })?;
+            analysis_state.record_deployment(header.slot, header.upgrade_authority);
+            upgrade_authority_kind = Self::classify_upgrade_authority(header.upgrade_authority, multisig_info);
+            loader::executable_bytes(&programdata)
+        } else {
+            // Other programs hold their code in the program account itself,
+            // which only the older loaders deploy
+            let owner = target_program_info.owner;
+            if !target_program_info.executable || (owner != &bpf_loader::id() && owner != &bpf_loader_deprecated::id()) {
+                msg!("Target is not a deployed program");
+                return Err(GuardError::InvalidTargetProgram.into());
+            }
+            &target_data
         };
 
         // Perform security analysis
-        let program_data = target_program_info.try_borrow_data()?;
-        let mut vulnerabilities = Vec::new();
-        let mut risk_score = 100u8;
+        let elf = Elf::parse(program_data).map_err(|err| {
+            msg!("Target is not a valid SBF program: {}", err);
+            GuardError::InvalidTargetProgram
+        })?;
+        if elf.text().is_none() {
+            msg!("Target program has no .text section");
+            return Err(GuardError::InvalidTargetProgram.into());
+        }
+        let mut findings = Vec::new();
+
+        // Upgrade authority check
+        match upgrade_authority_kind {
+            UpgradeAuthorityKind::SingleKey => {
+                findings.push(Finding::new(DetectorId::UpgradeAuthority, Severity::High));
+            }
+            UpgradeAuthorityKind::Multisig => {
+                findings.push(Finding::new(DetectorId::UpgradeAuthority, Severity::Low).with_code(1));
+            }
+            UpgradeAuthorityKind::Immutable => {}
+        }
 
         // Size check
         if program_data.len() > MAX_CONTRACT_SIZE {
-            vulnerabilities.push("Program size exceeds limit");
-            risk_score = risk_score.saturating_sub(10);
+            findings.push(Finding::new(DetectorId::ProgramSize, Severity::Medium));
         }
 
         // Pattern analysis
-        if Self::check_suspicious_patterns(&program_data) {
-            vulnerabilities.push("Suspicious instruction patterns detected");
-            risk_score = risk_score.saturating_sub(15);
+        let cfg = Cfg::from_elf(&elf).map_err(|err| {
+            msg!("Failed to build control-flow graph: {}", err);
+            GuardError::InvalidTargetProgram
+        })?;
+        let self_loops = Self::check_suspicious_patterns(&cfg);
+        for &pc in &self_loops {
+            findings.push(
+                Finding::new(DetectorId::SuspiciousPattern, Severity::Medium)
+                    .at(pc)
+                    .in_function(cfg.function_containing(pc).unwrap_or(0)),
+            );
+        }
+
+        // Control-flow analysis
+        let reachability = cfg.reachability();
+        if let Some((pc, count)) = Self::check_unreachable_code(&cfg, &reachability) {
+            msg!("{} unreachable instructions", count);
+            findings.push(
+                Finding::new(DetectorId::UnreachableCode, Severity::Low)
+                    .at(pc)
+                    .in_function(cfg.function_containing(pc).unwrap_or(0)),
+            );
+        }
+        for &entry in &reachability.non_terminating_functions {
+            // Already reported through its self-loop
+            if self_loops.iter().any(|&pc| cfg.function_containing(pc) == Some(entry)) {
+                continue;
+            }
+            findings.push(
+                Finding::new(DetectorId::NonTerminatingFunction, Severity::Medium)
+                    .at(entry)
+                    .in_function(entry),
+            );
+        }
+
+        // Account access analysis
+        let facts = input::analyze(&cfg);
+        for (pc, function) in Self::check_missing_signer(&facts.accesses) {
+            findings.push(
+                Finding::new(DetectorId::MissingSignerCheck, Severity::High)
+                    .at(pc)
+                    .in_function(function),
+            );
+        }
+        for (pc, function) in Self::check_missing_owner(&facts.accesses) {
+            findings.push(
+                Finding::new(DetectorId::MissingOwnerCheck, Severity::High)
+                    .at(pc)
+                    .in_function(function),
+            );
+        }
+        for (pc, function) in Self::check_type_cosplay(&facts.accesses) {
+            findings.push(
+                Finding::new(DetectorId::TypeCosplay, Severity::High)
+                    .at(pc)
+                    .in_function(function),
+            );
+        }
+        findings.extend(Self::check_arbitrary_cpi(&facts.invokes));
+        for (pc, function) in Self::check_account_revival(&facts.lamport_drains) {
+            findings.push(
+                Finding::new(DetectorId::AccountRevival, Severity::High)
+                    .at(pc)
+                    .in_function(function),
+            );
+        }
+        for (pc, function) in Self::check_bump_canonicalization(&facts.pda_calls) {
+            findings.push(
+                Finding::new(DetectorId::NonCanonicalBump, Severity::Medium)
+                    .at(pc)
+                    .in_function(function),
+            );
+        }
+        for arithmetic in &facts.arithmetic {
+            findings.push(
+                Finding::new(DetectorId::UncheckedArithmetic, Severity::Medium)
+                    .at(arithmetic.pc)
+                    .in_function(arithmetic.function),
+            );
+        }
+        if !Self::check_overflow_checks(&elf, &cfg) {
+            findings.push(Finding::new(DetectorId::UncheckedArithmetic, Severity::Low).with_code(1));
         }
 
         // Resource analysis
-        if Self::check_resource_usage(&program_data) {
-            vulnerabilities.push("Potential resource exhaustion detected");
-            risk_score = risk_score.saturating_sub(20);
+        let costs = compute::estimate(&cfg);
+        if let Some(cost) = costs.iter().find(|cost| cost.entry == cfg.entrypoint()) {
+            msg!(
+                "Estimated compute units: {}{}",
+                cost.worst_case,
+                if cost.unbounded { " before loops and recursion" } else { "" }
+            );
+            findings.extend(Self::check_resource_usage(cost, risk_model.compute_budget));
+        }
+
+        for finding in &findings {
+            msg!(
+                "Finding: {:?} ({:?}) at {:?} in function {:?}: {}",
+                finding.detector,
+                finding.severity,
+                finding.offset,
+                finding.function,
+                finding.recommendation()
+            );
         }
 
+        let safety_score = risk_model.score(&findings);
+        let passed = risk_model.passes(safety_score);
+
         // Update analysis state
-        analysis_state.update_analysis(risk_score, vulnerabilities.len() as u16, current_timestamp);
-        analysis_state.serialize(&mut *analysis_state_info.try_borrow_mut_data()?)?;
+        let mut analysis_data = analysis_state_info.try_borrow_mut_data()?;
+        let mut history = if fresh_analysis_state {
+            SecurityAnalysisState::init_history(&mut analysis_data)?
+        } else {
+            SecurityAnalysisState::history(&mut analysis_data)?
+        };
+        let vulnerability_count = findings.len();
+        analysis_state.update_analysis(
+            &mut history,
+            safety_score,
+            vulnerability_count as u16,
+            current_timestamp,
+        )?;
+        analysis_state.set_findings(findings);
+        analysis_state.set_verdict(passed);
+        analysis_state.set_syscalls(syscalls::inventory(&cfg));
+        analysis_state.set_pda_derivations(
+            facts
+                .pda_calls
+                .iter()
+                .map(|call| PdaDerivation {
+                    offset: call.pc as u32,
+                    syscall: call.syscall,
+                    seeds: call.seeds,
+                })
+                .collect(),
+        );
+        analysis_state.serialize(&mut &mut analysis_data[..])?;
 
-        msg!("Security analysis completed. Risk score: {}", risk_score);
-        msg!("Vulnerabilities found: {}", vulnerabilities.len());
+        for syscall in &analysis_state.syscalls {
+            msg!(
+                "Syscall {}: {} call sites",
+                syscall.name().unwrap_or("unknown"),
+                syscall.call_sites
+            );
+        }
+        msg!(
+            "Security analysis completed. Safety score: {} (risk level {:?})",
+            safety_score,
+            safety_score.risk_level()
+        );
+        msg!("Verdict: {}", if passed { "pass" } else { "fail" });
+        msg!("Vulnerabilities found: {}", vulnerability_count);
         
         Ok(())
     }
@@ -123,30 +416,25 @@ impl Processor {
         
         let metrics_state_info = next_account_info(account_info_iter)?;
         let authority_info = next_account_info(account_info_iter)?;
+        let target_program_info = next_account_info(account_info_iter)?;
 
         // Validate accounts
         if !authority_info.is_signer {
             return Err(GuardError::UnauthorizedAccount.into());
         }
 
-        if metrics_state_info.owner != program_id {
-            return Err(GuardError::InvalidAccountData.into());
-        }
+        let mut metrics_state = Self::load_metrics_state(program_id, metrics_state_info, target_program_info.key)?;
 
         // Get current timestamp
         let clock = Clock::get()?;
         let current_timestamp = clock.unix_timestamp;
 
-        // Initialize or update metrics
-        let mut metrics_state = if metrics_state_info.data_len() > 0 {
-            MetricsState::try_from_slice(&metrics_state_info.data.borrow())?
-        } else {
-            MetricsState::new(current_timestamp)
-        };
-
-        // Update metrics
-        metrics_state.record_transaction(gas_used, success, current_timestamp);
-        metrics_state.serialize(&mut *metrics_state_info.try_borrow_mut_data()?)?;
+        // Update metrics; only the fixed fields and one history slot are
+        // read and written
+        let mut metrics_data = metrics_state_info.try_borrow_mut_data()?;
+        let mut history = MetricsState::history(&mut metrics_data)?;
+        metrics_state.record_transaction(&mut history, gas_used, success, current_timestamp)?;
+        metrics_state.serialize(&mut &mut metrics_data[..])?;
 
         if gas_used > GAS_WARNING_THRESHOLD {
             msg!("Warning: High gas usage detected: {}", gas_used);
@@ -155,6 +443,52 @@ impl Processor {
         Ok(())
     }
 
+    fn process_initialize_metrics(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        history_capacity: u32,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let metrics_state_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let target_program_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if !payer_info.is_signer {
+            return Err(GuardError::UnauthorizedAccount.into());
+        }
+
+        if system_program_info.key != &system_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        if history_capacity == 0 {
+            msg!("History capacity must be at least 1");
+            return Err(GuardError::InvalidInstructionData.into());
+        }
+
+        let bump = Self::check_new_metrics_address(program_id, metrics_state_info, target_program_info.key)?;
+        Self::create_pda_account(
+            program_id,
+            metrics_state_info,
+            payer_info,
+            system_program_info,
+            MetricsState::space(history_capacity as usize),
+            &[METRICS_SEED, target_program_info.key.as_ref(), &[bump]],
+        )?;
+
+        let clock = Clock::get()?;
+        let mut metrics_data = metrics_state_info.try_borrow_mut_data()?;
+        MetricsState::init_history(&mut metrics_data)?;
+        MetricsState::new(*target_program_info.key, bump, clock.unix_timestamp)
+            .serialize(&mut &mut metrics_data[..])?;
+
+        msg!("Metrics initialized for {}", target_program_info.key);
+
+        Ok(())
+    }
+
     fn process_update_network_stats(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
@@ -166,143 +500,2226 @@ impl Processor {
         let network_stats_info = next_account_info(account_info_iter)?;
         let authority_info = next_account_info(account_info_iter)?;
 
-        if !authority_info.is_signer {
-            return Err(GuardError::UnauthorizedAccount.into());
-        }
-
-        if network_stats_info.owner != program_id {
-            return Err(GuardError::InvalidAccountData.into());
-        }
+        let mut stats = Self::load_authorized_program_state(
+            program_id,
+            network_stats_info,
+            authority_info,
+        )?;
 
         let clock = Clock::get()?;
         let current_timestamp = clock.unix_timestamp;
 
-        let mut stats = ProgramState::try_from_slice(&network_stats_info.data.borrow())?;
         stats.update_network_stats(transactions_per_second, average_block_time, current_timestamp)?;
-        stats.serialize(&mut *network_stats_info.try_borrow_mut_data()?)?;
+        stats.serialize(&mut &mut network_stats_info.try_borrow_mut_data()?[..])?;
 
         Ok(())
     }
 
-    fn check_suspicious_patterns(program_data: &[u8]) -> bool {
-        program_data.windows(4).any(|window| {
-            matches!(window, &[0x48, 0x31, 0xc0, 0x90])
-        }) || program_data.windows(2).any(|window| {
-            matches!(window, &[0xeb, 0xfe])
-        })
+    fn process_propose_authority(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        new_authority: Pubkey,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let program_state_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+
+        let mut state = Self::load_authorized_program_state(
+            program_id,
+            program_state_info,
+            authority_info,
+        )?;
+
+        state.propose_authority(new_authority);
+        state.serialize(&mut &mut program_state_info.try_borrow_mut_data()?[..])?;
+
+        msg!("Authority handover proposed to {}", new_authority);
+
+        Ok(())
     }
 
-    fn check_resource_usage(program_data: &[u8]) -> bool {
-        let mut instruction_count = 0;
-        let mut memory_ops = 0;
+    fn process_accept_authority(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
 
-        for window in program_data.windows(2) {
-            match window {
-                [0x48, 0x89] => memory_ops += 1,
-                [0xe8, _] => instruction_count += 1,
-                _ => {}
-            }
+        let program_state_info = next_account_info(account_info_iter)?;
+        let pending_authority_info = next_account_info(account_info_iter)?;
+
+        if !pending_authority_info.is_signer {
+            return Err(GuardError::UnauthorizedAccount.into());
         }
 
-        instruction_count > 1000 || memory_ops > 500
+        let mut state = Self::load_program_state(program_id, program_state_info)?;
+        state.accept_authority(pending_authority_info.key)?;
+        state.serialize(&mut &mut program_state_info.try_borrow_mut_data()?[..])?;
+
+        msg!("Authority handed over to {}", pending_authority_info.key);
+
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    
-    #[test]
-    fn test_analyze_contract() {
-        let program_id = Pubkey::new_unique();
-        let mut lamports = 0;
-        let mut program_data = vec![0; 100];
-        let mut state_data = vec![0; 1000];
-        
-        let target_program_pubkey = Pubkey::new_unique();
-        let target_program = AccountInfo::new(
-            &target_program_pubkey,
-            false,
-            true,
-            &mut lamports,
-            &mut program_data,
-            &program_id,
-            false,
-            Clock::default().epoch,
-        );
+    fn process_renounce_authority(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
 
-        let mut analysis_state_lamports = 0;
-        let analysis_state_pubkey = Pubkey::new_unique();
-        let analysis_state = AccountInfo::new(
-            &analysis_state_pubkey,
-            false,
-            true,
-            &mut analysis_state_lamports,
-            &mut state_data,
-            &program_id,
-            false,
-            Clock::default().epoch,
-        );
+        let program_state_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
 
-        let mut authority_lamports = 0;
-        let mut authority_data = vec![0; 0];
-        let authority_pubkey = Pubkey::new_unique();
-        let authority = AccountInfo::new(
-            &authority_pubkey,
-            true,
-            true,
-            &mut authority_lamports,
-            &mut authority_data,
-            &program_id,
-            false,
-            Clock::default().epoch,
-        );
+        let mut state = Self::load_authorized_program_state(
+            program_id,
+            program_state_info,
+            authority_info,
+        )?;
 
-        let mut program_state_lamports = 0;
-        let program_state_pubkey = Pubkey::new_unique();
-        let mut program_state_data = vec![0; 1000];
-        let program_state = AccountInfo::new(
-            &program_state_pubkey,
-            false,
-            true,
-            &mut program_state_lamports,
-            &mut program_state_data,
-            &program_id,
-            false,
-            Clock::default().epoch,
-        );
+        state.renounce_authority();
+        state.serialize(&mut &mut program_state_info.try_borrow_mut_data()?[..])?;
 
-        let accounts = vec![
-            target_program,
-            analysis_state,
-            authority,
-            program_state,
-        ];
+        msg!("Authority renounced, program settings are now immutable");
 
-        let result = Processor::process_analyze_contract(
-            &program_id,
-            &accounts,
-            100,
-        );
+        Ok(())
+    }
 
-        assert!(result.is_ok());
+    fn process_update_risk_model(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        risk_model: RiskModel,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let program_state_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+
+        let mut state = Self::load_authorized_program_state(
+            program_id,
+            program_state_info,
+            authority_info,
+        )?;
+
+        risk_model.validate()?;
+        state.risk_model = risk_model;
+        state.serialize(&mut &mut program_state_info.try_borrow_mut_data()?[..])?;
+
+        msg!("Risk model updated");
+
+        Ok(())
     }
 
-    #[test]
-    fn test_suspicious_patterns() {
-        let safe_data = vec![0x90, 0x90, 0x90, 0x90];
-        let unsafe_data = vec![0x48, 0x31, 0xc0, 0x90];
-        
-        assert!(!Processor::check_suspicious_patterns(&safe_data));
-        assert!(Processor::check_suspicious_patterns(&unsafe_data));
+    fn process_migrate_account(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        account_type: AccountType,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let account_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if !payer_info.is_signer {
+            return Err(GuardError::UnauthorizedAccount.into());
+        }
+
+        if system_program_info.key != &system_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        if account_info.owner != program_id {
+            return Err(GuardError::InvalidAccountData.into());
+        }
+
+        // v1 accounts cannot tell their type, so the program state PDA is the
+        // only account that may be read as program state
+        let (program_state_address, _) = ProgramState::find_address(program_id);
+        if (account_info.key == &program_state_address) != (account_type == AccountType::ProgramState) {
+            msg!("Account type does not match the account address");
+            return Err(GuardError::InvalidAccountData.into());
+        }
+
+        match account_type {
+            AccountType::ProgramState => {
+                Self::migrate::<ProgramState>(account_info, payer_info, system_program_info, ProgramState::SPACE)
+            }
+            AccountType::SecurityAnalysis => Self::migrate::<SecurityAnalysisState>(
+                account_info,
+                payer_info,
+                system_program_info,
+                SecurityAnalysisState::SPACE,
+            ),
+            AccountType::Metrics => {
+                Self::migrate::<MetricsState>(account_info, payer_info, system_program_info, MetricsState::SPACE)
+            }
+        }
     }
 
-    #[test]
-    fn test_resource_usage() {
-        let low_usage = vec![0x90, 0x90];
-        let high_usage = vec![0x48, 0x89, 255];
-        
-        assert!(!Processor::check_resource_usage(&low_usage));
-        assert!(Processor::check_resource_usage(&high_usage));
+    /// Rewrites `account_info` in the current layout of `T`, reallocating it to
+    /// at least `space` bytes and having `payer_info` cover the extra rent
+    fn migrate<'a, T: VersionedAccount>(
+        account_info: &AccountInfo<'a>,
+        payer_info: &AccountInfo<'a>,
+        system_program_info: &AccountInfo<'a>,
+        space: usize,
+    ) -> ProgramResult {
+        let header = AccountHeader::read(&account_info.data.borrow(), T::ACCOUNT_TYPE);
+        if header.is_some_and(|header| header.version == ACCOUNT_LAYOUT_VERSION) {
+            msg!("Account already has layout v{}", ACCOUNT_LAYOUT_VERSION);
+            return Ok(());
+        }
+        AccountHeader::legacy_body(&account_info.data.borrow(), T::ACCOUNT_TYPE)?;
+
+        let new_len = account_info.data_len().max(space);
+        account_info.realloc(new_len, true)?;
+
+        let shortfall = Rent::get()?
+            .minimum_balance(new_len)
+            .saturating_sub(account_info.lamports());
+        if shortfall > 0 {
+            invoke(
+                &system_instruction::transfer(payer_info.key, account_info.key, shortfall),
+                &[payer_info.clone(), account_info.clone(), system_program_info.clone()],
+            )?;
+        }
+
+        T::upgrade(&mut account_info.try_borrow_mut_data()?)?;
+
+        msg!("Migrated {:?} account to layout v{}", T::ACCOUNT_TYPE, ACCOUNT_LAYOUT_VERSION);
+
+        Ok(())
     }
-}
\ No newline at end of file
+
+    fn process_migrate_metrics(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        history_capacity: u32,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let legacy_metrics_info = next_account_info(account_info_iter)?;
+        let metrics_state_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let program_state_info = next_account_info(account_info_iter)?;
+        let target_program_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        // Legacy accounts do not record their target, so only the authority
+        // may say which program they measured
+        Self::load_authorized_program_state(program_id, program_state_info, authority_info)?;
+
+        if system_program_info.key != &system_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        // v1 accounts cannot tell their type, but the program state PDA never
+        // holds metrics
+        if legacy_metrics_info.owner != program_id || legacy_metrics_info.key == program_state_info.key {
+            return Err(GuardError::InvalidAccountData.into());
+        }
+
+        if history_capacity == 0 {
+            msg!("History capacity must be at least 1");
+            return Err(GuardError::InvalidInstructionData.into());
+        }
+
+        let bump = Self::check_new_metrics_address(program_id, metrics_state_info, target_program_info.key)?;
+        let (metrics_state, gas_history) = MetricsState::from_legacy(
+            &legacy_metrics_info.try_borrow_data()?,
+            *target_program_info.key,
+            bump,
+        )?;
+
+        // Close the old account into the new one; the program owns it, so
+        // its lamports move directly
+        legacy_metrics_info.try_borrow_mut_data()?.fill(0);
+        let lamports = legacy_metrics_info.lamports();
+        **legacy_metrics_info.try_borrow_mut_lamports()? = 0;
+        **metrics_state_info.try_borrow_mut_lamports()? += lamports;
+
+        let capacity = history_capacity as usize;
+        Self::create_pda_account(
+            program_id,
+            metrics_state_info,
+            authority_info,
+            system_program_info,
+            MetricsState::space(capacity),
+            &[METRICS_SEED, target_program_info.key.as_ref(), &[bump]],
+        )?;
+
+        let mut metrics_data = metrics_state_info.try_borrow_mut_data()?;
+        metrics_state.serialize(&mut &mut metrics_data[..])?;
+        let mut history = MetricsState::init_history(&mut metrics_data)?;
+        for metric in &gas_history[gas_history.len().saturating_sub(capacity)..] {
+            history.push(metric)?;
+        }
+
+        msg!("Migrated metrics of {} to layout v{}", target_program_info.key, ACCOUNT_LAYOUT_VERSION);
+
+        Ok(())
+    }
+
+    fn process_resize_history(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        capacity: u32,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let account_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let program_state_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let refund_recipient_info = next_account_info(account_info_iter)?;
+
+        Self::load_authorized_program_state(program_id, program_state_info, authority_info)?;
+
+        if system_program_info.key != &system_program::id() {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        if account_info.owner != program_id {
+            return Err(GuardError::InvalidAccountData.into());
+        }
+
+        if capacity == 0 {
+            msg!("History capacity must be at least 1");
+            return Err(GuardError::InvalidInstructionData.into());
+        }
+
+        let account_type = AccountHeader::account_type(&account_info.data.borrow());
+        match account_type {
+            Some(AccountType::SecurityAnalysis) => Self::resize_history::<SecurityAnalysisState>(
+                account_info,
+                authority_info,
+                refund_recipient_info,
+                system_program_info,
+                capacity as usize,
+            ),
+            Some(AccountType::Metrics) => Self::resize_history::<MetricsState>(
+                account_info,
+                authority_info,
+                refund_recipient_info,
+                system_program_info,
+                capacity as usize,
+            ),
+            _ => {
+                msg!("Account has no history");
+                Err(GuardError::InvalidAccountData.into())
+            }
+        }
+    }
+
+    /// Reallocates `account_info` to hold `capacity` history entries, keeping
+    /// the most recent ones. `payer_info` covers any rent the balance falls
+    /// short of and `refund_recipient_info` receives any excess.
+    fn resize_history<'a, T: HistoryAccount>(
+        account_info: &AccountInfo<'a>,
+        payer_info: &AccountInfo<'a>,
+        refund_recipient_info: &AccountInfo<'a>,
+        system_program_info: &AccountInfo<'a>,
+        capacity: usize,
+    ) -> ProgramResult {
+        let mut data = account_info.try_borrow_mut_data()?;
+        T::load(&data)?;
+        let entries: Vec<T::Entry> = T::history(&mut data)?.iter().collect();
+        drop(data);
+
+        let new_len = T::space(capacity);
+        account_info.realloc(new_len, false)?;
+
+        let mut data = account_info.try_borrow_mut_data()?;
+        let mut history = T::init_history(&mut data)?;
+        for entry in &entries[entries.len().saturating_sub(capacity)..] {
+            history.push(entry)?;
+        }
+        drop(data);
+
+        let required_lamports = Rent::get()?.minimum_balance(new_len);
+        let lamports = account_info.lamports();
+        if lamports < required_lamports {
+            invoke(
+                &system_instruction::transfer(payer_info.key, account_info.key, required_lamports - lamports),
+                &[payer_info.clone(), account_info.clone(), system_program_info.clone()],
+            )?;
+        } else {
+            // The program owns the account, so it can return the excess directly
+            let refund = lamports - required_lamports;
+            **account_info.try_borrow_mut_lamports()? -= refund;
+            **refund_recipient_info.try_borrow_mut_lamports()? += refund;
+        }
+
+        msg!("History resized to {} entries", capacity);
+
+        Ok(())
+    }
+
+    /// Checks that `programdata_info` is the ProgramData account referenced by
+    /// an upgradeable program account
+    fn resolve_programdata<'a, 'b>(
+        program_account_data: &[u8],
+        programdata_info: Option<&'a AccountInfo<'b>>,
+    ) -> Result<&'a AccountInfo<'b>, ProgramError> {
+        let programdata_address = loader::programdata_address(program_account_data).ok_or_else(|| {
+            msg!("Upgradeable target is not a program account");
+            GuardError::InvalidTargetProgram
+        })?;
+        let programdata_info = programdata_info.ok_or_else(|| {
+            msg!("Missing ProgramData account for upgradeable target");
+            GuardError::InvalidTargetProgram
+        })?;
+
+        if programdata_info.key != &programdata_address
+            || programdata_info.owner != &bpf_loader_upgradeable::id()
+        {
+            msg!("ProgramData account does not belong to the target program");
+            return Err(GuardError::InvalidTargetProgram.into());
+        }
+
+        Ok(programdata_info)
+    }
+
+    /// Classifies the target's upgrade authority. The multisig account is
+    /// optional; without it, or when the authority is not one of its signers,
+    /// a live authority is treated as a single key.
+    fn classify_upgrade_authority(
+        upgrade_authority: Option<Pubkey>,
+        multisig_info: Option<&AccountInfo>,
+    ) -> UpgradeAuthorityKind {
+        let multisig = multisig_info.map(|info| (info.key, info.owner));
+        UpgradeAuthorityKind::classify(upgrade_authority.as_ref(), multisig)
+    }
+
+    /// Loads the program state, verifying it lives at the canonical PDA
+    fn load_program_state(
+        program_id: &Pubkey,
+        program_state_info: &AccountInfo,
+    ) -> Result<ProgramState, ProgramError> {
+        if program_state_info.owner != program_id {
+            return Err(GuardError::InvalidAccountData.into());
+        }
+
+        let state = ProgramState::load(&program_state_info.data.borrow())?;
+        let expected_address = Pubkey::create_program_address(
+            &[PROGRAM_STATE_SEED, &[state.bump]],
+            program_id,
+        )?;
+        if program_state_info.key != &expected_address {
+            msg!("Program state account is not the canonical PDA");
+            return Err(GuardError::InvalidAccountData.into());
+        }
+
+        Ok(state)
+    }
+
+    /// Loads the metrics of `target_program`, checking that `metrics_state_info`
+    /// is their PDA
+    fn load_metrics_state(
+        program_id: &Pubkey,
+        metrics_state_info: &AccountInfo,
+        target_program: &Pubkey,
+    ) -> Result<MetricsState, ProgramError> {
+        if metrics_state_info.owner != program_id {
+            return Err(GuardError::InvalidAccountData.into());
+        }
+
+        let state = MetricsState::load(&metrics_state_info.data.borrow())?;
+        if &state.target_program != target_program {
+            msg!("Metrics account belongs to another program");
+            return Err(GuardError::InvalidAccountData.into());
+        }
+        let expected_address = Pubkey::create_program_address(
+            &[METRICS_SEED, target_program.as_ref(), &[state.bump]],
+            program_id,
+        )?;
+        if metrics_state_info.key != &expected_address {
+            msg!("Metrics account is not the PDA of the target program");
+            return Err(GuardError::InvalidAccountData.into());
+        }
+
+        Ok(state)
+    }
+
+    /// Checks that `metrics_state_info` is the metrics PDA of `target_program`
+    /// and not yet created, returning its bump
+    fn check_new_metrics_address(
+        program_id: &Pubkey,
+        metrics_state_info: &AccountInfo,
+        target_program: &Pubkey,
+    ) -> Result<u8, ProgramError> {
+        let (expected_address, bump) = MetricsState::find_address(target_program, program_id);
+        if metrics_state_info.key != &expected_address {
+            msg!("Metrics account is not the PDA of the target program");
+            return Err(GuardError::InvalidAccountData.into());
+        }
+
+        if metrics_state_info.owner == program_id || !metrics_state_info.data_is_empty() {
+            msg!("Metrics already initialized");
+            return Err(GuardError::InitializationFailed.into());
+        }
+
+        Ok(bump)
+    }
+
+    /// Creates `account_info` at the PDA of `signer_seeds` with `space` bytes,
+    /// owned by this program. `payer_info` funds the rent-exempt balance,
+    /// less any lamports already sent to the address.
+    fn create_pda_account<'a>(
+        program_id: &Pubkey,
+        account_info: &AccountInfo<'a>,
+        payer_info: &AccountInfo<'a>,
+        system_program_info: &AccountInfo<'a>,
+        space: usize,
+        signer_seeds: &[&[u8]],
+    ) -> ProgramResult {
+        let rent = Rent::get()?;
+        let required_lamports = rent.minimum_balance(space);
+        let account_infos = [
+            payer_info.clone(),
+            account_info.clone(),
+            system_program_info.clone(),
+        ];
+
+        if account_info.lamports() == 0 {
+            invoke_signed(
+                &system_instruction::create_account(
+                    payer_info.key,
+                    account_info.key,
+                    required_lamports,
+                    space as u64,
+                    program_id,
+                ),
+                &account_infos,
+                &[signer_seeds],
+            )?;
+        } else {
+            let shortfall = required_lamports.saturating_sub(account_info.lamports());
+            if shortfall > 0 {
+                invoke(
+                    &system_instruction::transfer(payer_info.key, account_info.key, shortfall),
+                    &account_infos,
+                )?;
+            }
+            invoke_signed(
+                &system_instruction::allocate(account_info.key, space as u64),
+                &account_infos,
+                &[signer_seeds],
+            )?;
+            invoke_signed(
+                &system_instruction::assign(account_info.key, program_id),
+                &account_infos,
+                &[signer_seeds],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Loads the program state and verifies that `authority_info` is its
+    /// stored authority and has signed. Every privileged instruction goes
+    /// through here.
+    fn load_authorized_program_state(
+        program_id: &Pubkey,
+        program_state_info: &AccountInfo,
+        authority_info: &AccountInfo,
+    ) -> Result<ProgramState, ProgramError> {
+        if !authority_info.is_signer {
+            return Err(GuardError::UnauthorizedAccount.into());
+        }
+
+        let state = Self::load_program_state(program_id, program_state_info)?;
+        state.check_authority(authority_info.key)?;
+        Ok(state)
+    }
+
+    /// Pcs of `ja` instructions that unconditionally jump back to the start
+    /// of their own block
+    fn check_suspicious_patterns(cfg: &Cfg) -> Vec<usize> {
+        cfg.blocks()
+            .filter(|block| block.is_self_loop())
+            .map(|block| block.last)
+            .collect()
+    }
+
+    /// First pc and instruction count of code no function can reach
+    ///
+    /// Compilers pad aborting paths with extra calls to `abort`, so blocks that
+    /// consist of a single aborting call are not reported.
+    fn check_unreachable_code(cfg: &Cfg, reachability: &Reachability) -> Option<(usize, usize)> {
+        let mut first = None;
+        let mut count = 0;
+        for block in cfg.blocks() {
+            let padding = block.exit == BlockExit::Abort && block.start == block.last;
+            if reachability.is_reachable(block.start) || padding {
+                continue;
+            }
+            first.get_or_insert(block.start);
+            count += block.end - block.start;
+        }
+        first.map(|pc| (pc, count))
+    }
+
+    /// First write to account data or lamports in each function that no
+    /// comparison of any account's `is_signer` flag dominates, as
+    /// `(pc, function)`
+    fn check_missing_signer(accesses: &[AccountAccess]) -> Vec<(usize, usize)> {
+        Self::first_unchecked_per_function(
+            accesses,
+            |access| {
+                access.is_store
+                    && matches!(
+                        access.field,
+                        AccountField::Discriminator | AccountField::Data | AccountField::Lamports
+                    )
+            },
+            |access| access.compared.contains(AccountField::IsSigner),
+        )
+    }
+
+    /// First read of account data in each function that no comparison of
+    /// any account's owner dominates, as `(pc, function)`
+    fn check_missing_owner(accesses: &[AccountAccess]) -> Vec<(usize, usize)> {
+        Self::first_unchecked_per_function(
+            accesses,
+            |access| {
+                !access.is_store && matches!(access.field, AccountField::Discriminator | AccountField::Data)
+            },
+            |access| access.compared.contains(AccountField::Owner),
+        )
+    }
+
+    /// First read of account data past the discriminator in each function
+    /// that no comparison of an account's discriminator dominates, as
+    /// `(pc, function)`
+    fn check_type_cosplay(accesses: &[AccountAccess]) -> Vec<(usize, usize)> {
+        Self::first_unchecked_per_function(
+            accesses,
+            |access| !access.is_store && access.field == AccountField::Data,
+            |access| access.compared.contains(AccountField::Discriminator),
+        )
+    }
+
+    /// Invocations of a program id taken from an account key that was never
+    /// compared, and invocations whose program id could not be resolved
+    fn check_arbitrary_cpi(invokes: &[Invoke]) -> Vec<Finding> {
+        invokes
+            .iter()
+            .filter_map(|invoke| {
+                let finding = match invoke.program_id {
+                    ProgramIdSource::Constant => return None,
+                    ProgramIdSource::AccountKey if invoke.compared.contains(AccountField::Key) => {
+                        return None
+                    }
+                    ProgramIdSource::AccountKey => Finding::new(DetectorId::ArbitraryCpi, Severity::Critical),
+                    ProgramIdSource::Unknown => {
+                        Finding::new(DetectorId::ArbitraryCpi, Severity::Info).with_code(1)
+                    }
+                };
+                Some(finding.at(invoke.pc).in_function(invoke.function))
+            })
+            .collect()
+    }
+
+    /// First lamport drain in each function that closes an account without
+    /// wiping its data, as `(pc, function)`
+    fn check_account_revival(drains: &[LamportDrain]) -> Vec<(usize, usize)> {
+        let mut unwiped: Vec<(usize, usize)> = Vec::new();
+        for drain in drains.iter().filter(|drain| !drain.data_wiped) {
+            if !unwiped.iter().any(|(_, function)| *function == drain.function) {
+                unwiped.push((drain.pc, drain.function));
+            }
+        }
+        unwiped
+    }
+
+    /// `sol_create_program_address` calls with a bump the caller chose that
+    /// no canonical bump search on the same seeds precedes, as
+    /// `(pc, function)`. Bumps kept in account data are trusted as stored by
+    /// the program.
+    fn check_bump_canonicalization(pda_calls: &[PdaCall]) -> Vec<(usize, usize)> {
+        pda_calls
+            .iter()
+            .filter(|call| call.bump == Some(BumpSource::CallerControlled) && !call.bump_found)
+            .map(|call| (call.pc, call.function))
+            .collect()
+    }
+
+    /// Returns false when the program does arithmetic on registers but its
+    /// read-only data lacks the panic messages of overflow-checked arithmetic
+    fn check_overflow_checks(elf: &Elf, cfg: &Cfg) -> bool {
+        let does_arithmetic = cfg.decoder().any(|decoded| {
+            matches!(
+                decoded.insn,
+                Insn::Alu {
+                    op: AluOp::Add | AluOp::Sub | AluOp::Mul,
+                    is_64: true,
+                    src: Operand::Reg(_),
+                    ..
+                }
+            )
+        });
+        let rodata = elf.rodata().map_or(&[][..], |section| section.data);
+        !does_arithmetic
+            || OVERFLOW_PANIC_MESSAGES
+                .iter()
+                .any(|message| rodata.windows(message.len()).any(|window| window == *message))
+    }
+
+    fn first_unchecked_per_function(
+        accesses: &[AccountAccess],
+        is_guarded: impl Fn(&AccountAccess) -> bool,
+        is_checked: impl Fn(&AccountAccess) -> bool,
+    ) -> Vec<(usize, usize)> {
+        let mut unchecked: Vec<(usize, usize)> = Vec::new();
+        for access in accesses {
+            if !is_guarded(access) || is_checked(access) {
+                continue;
+            }
+            if !unchecked.iter().any(|(_, function)| *function == access.function) {
+                unchecked.push((access.pc, access.function));
+            }
+        }
+        unchecked
+    }
+
+    /// Compares the estimated cost of the entrypoint with the budget and the
+    /// hard compute unit limit
+    fn check_resource_usage(cost: &FunctionCost, compute_budget: u32) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        if cost.worst_case > MAX_COMPUTE_UNIT_LIMIT as u64 {
+            findings.push(Finding::new(DetectorId::ResourceUsage, Severity::Critical).with_code(2));
+        } else if cost.worst_case > compute_budget as u64 {
+            findings.push(Finding::new(DetectorId::ResourceUsage, Severity::High));
+        }
+        if cost.unbounded {
+            findings.push(Finding::new(DetectorId::ResourceUsage, Severity::Info).with_code(1));
+        }
+        findings
+            .into_iter()
+            .map(|finding| finding.in_function(cost.entry))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use borsh::BorshDeserialize;
+    use crate::constants::SQUADS_V4_PROGRAM_ID;
+    use crate::state::{
+        AnalysisResult, AnalysisStatus, GasMetric, LegacyMetricsState, LegacyProgramState, LegacySecurityAnalysisState,
+    };
+    use crate::analysis::{
+        cfg::ExternalCall,
+        input::{FieldSet, PdaSyscall, SeedsPattern},
+        sbpf::SbpfVersion,
+        syscalls::Capabilities,
+    };
+    use crate::state::SafetyScore;
+    use crate::analysis::{
+        elf::test_utils::ElfBuilder,
+        loader::test_utils::{program_account, programdata_account},
+        sbpf::test_utils::slot,
+    };
+    use solana_program::{
+        entrypoint::{deserialize, MAX_PERMITTED_DATA_INCREASE, NON_DUP_MARKER, SUCCESS},
+        instruction::Instruction,
+        program_stubs::{set_syscall_stubs, SyscallStubs},
+    };
+    use std::sync::Once;
+
+    const TEST_TIMESTAMP: i64 = 1_700_000_000;
+
+    /// Serves sysvars and emulates the system program for processor tests
+    struct TestSyscallStubs;
+
+    impl SyscallStubs for TestSyscallStubs {
+        fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+            let clock = Clock {
+                unix_timestamp: TEST_TIMESTAMP,
+                ..Clock::default()
+            };
+            unsafe { *(var_addr as *mut Clock) = clock };
+            SUCCESS
+        }
+
+        fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+            unsafe { *(var_addr as *mut Rent) = Rent::default() };
+            SUCCESS
+        }
+
+        fn sol_invoke_signed(
+            &self,
+            instruction: &Instruction,
+            account_infos: &[AccountInfo],
+            _signers_seeds: &[&[&[u8]]],
+        ) -> ProgramResult {
+            assert_eq!(instruction.program_id, system_program::id());
+            let find = |index: usize| {
+                account_infos
+                    .iter()
+                    .find(|info| info.key == &instruction.accounts[index].pubkey)
+                    .unwrap()
+            };
+            let data = &instruction.data;
+            let read_u64 = |at: usize| u64::from_le_bytes(data[at..at + 8].try_into().unwrap());
+            let allocate = |info: &AccountInfo, space: u64| {
+                *info.data.borrow_mut() = Box::leak(vec![0; space as usize].into_boxed_slice());
+            };
+            let transfer = |from: &AccountInfo, to: &AccountInfo, lamports: u64| {
+                **from.lamports.borrow_mut() -= lamports;
+                **to.lamports.borrow_mut() += lamports;
+            };
+
+            match u32::from_le_bytes(data[..4].try_into().unwrap()) {
+                // CreateAccount { lamports, space, owner }
+                0 => {
+                    let (from, to) = (find(0), find(1));
+                    transfer(from, to, read_u64(4));
+                    allocate(to, read_u64(12));
+                    to.assign(&Pubkey::new_from_array(data[20..52].try_into().unwrap()));
+                }
+                // Assign { owner }
+                1 => find(0).assign(&Pubkey::new_from_array(data[4..36].try_into().unwrap())),
+                // Transfer { lamports }
+                2 => transfer(find(0), find(1), read_u64(4)),
+                // Allocate { space }
+                8 => allocate(find(0), read_u64(4)),
+                other => panic!("unexpected system instruction {}", other),
+            }
+            Ok(())
+        }
+    }
+
+    fn setup() {
+        static STUBS: Once = Once::new();
+        STUBS.call_once(|| {
+            set_syscall_stubs(Box::new(TestSyscallStubs));
+        });
+    }
+
+    /// Lays out `(key, is_signer, owner, lamports, data)` writable accounts the
+    /// way the runtime passes them to a program, leaving room for
+    /// `AccountInfo::realloc` to grow their data in place
+    fn serialize_input(accounts: &[(Pubkey, bool, Pubkey, u64, Vec<u8>)]) -> Vec<u64> {
+        let mut bytes = (accounts.len() as u64).to_le_bytes().to_vec();
+        for (key, is_signer, owner, lamports, data) in accounts {
+            bytes.extend_from_slice(&[NON_DUP_MARKER, *is_signer as u8, 1, 0]);
+            bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(key.as_ref());
+            bytes.extend_from_slice(owner.as_ref());
+            bytes.extend_from_slice(&lamports.to_le_bytes());
+            bytes.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(data);
+            bytes.resize((bytes.len() + MAX_PERMITTED_DATA_INCREASE).next_multiple_of(8), 0);
+            bytes.extend_from_slice(&Clock::default().epoch.to_le_bytes());
+        }
+        // Empty instruction data and the program id
+        bytes.resize(bytes.len() + 8 + 32, 0);
+        bytes
+            .chunks(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_initialize() {
+        setup();
+        let program_id = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let system_program_id = system_program::id();
+        let (state_pubkey, bump) = ProgramState::find_address(&program_id);
+
+        // Kept separate from `system_program_id` since the owner is reassigned in place
+        let state_owner = system_program::id();
+        let mut state_lamports = 0;
+        let mut state_data = vec![0; 0];
+        let program_state = AccountInfo::new(
+            &state_pubkey,
+            false,
+            true,
+            &mut state_lamports,
+            &mut state_data,
+            &state_owner,
+            false,
+            Clock::default().epoch,
+        );
+
+        let payer_pubkey = Pubkey::new_unique();
+        let mut payer_lamports = 1_000_000_000;
+        let mut payer_data = vec![0; 0];
+        let payer = AccountInfo::new(
+            &payer_pubkey,
+            true,
+            true,
+            &mut payer_lamports,
+            &mut payer_data,
+            &system_program_id,
+            false,
+            Clock::default().epoch,
+        );
+
+        let mut system_lamports = 0;
+        let mut system_data = vec![0; 0];
+        let system_program = AccountInfo::new(
+            &system_program_id,
+            false,
+            false,
+            &mut system_lamports,
+            &mut system_data,
+            &system_program_id,
+            true,
+            Clock::default().epoch,
+        );
+
+        let accounts = vec![program_state, payer, system_program];
+        let instruction = borsh::to_vec(&GuardInstruction::Initialize { authority }).unwrap();
+
+        assert!(Processor::process(&program_id, &accounts, &instruction).is_ok());
+        assert_eq!(accounts[0].owner, &program_id);
+        assert_eq!(accounts[0].lamports(), Rent::default().minimum_balance(ProgramState::SPACE));
+
+        let state = ProgramState::deserialize(&mut &accounts[0].data.borrow()[..]).unwrap();
+        assert_eq!(state.authority, authority);
+        assert_eq!(state.bump, bump);
+        assert_eq!(state.initialized_at, TEST_TIMESTAMP);
+
+        // A second initialization must be rejected
+        assert_eq!(
+            Processor::process(&program_id, &accounts, &instruction),
+            Err(GuardError::InitializationFailed.into())
+        );
+    }
+
+    #[test]
+    fn test_initialize_rejects_non_canonical_address() {
+        setup();
+        let program_id = Pubkey::new_unique();
+        let system_program_id = system_program::id();
+
+        let state_pubkey = Pubkey::new_unique();
+        let mut state_lamports = 0;
+        let mut state_data = vec![0; 0];
+        let program_state = AccountInfo::new(
+            &state_pubkey,
+            false,
+            true,
+            &mut state_lamports,
+            &mut state_data,
+            &system_program_id,
+            false,
+            Clock::default().epoch,
+        );
+
+        let payer_pubkey = Pubkey::new_unique();
+        let mut payer_lamports = 1_000_000_000;
+        let mut payer_data = vec![0; 0];
+        let payer = AccountInfo::new(
+            &payer_pubkey,
+            true,
+            true,
+            &mut payer_lamports,
+            &mut payer_data,
+            &system_program_id,
+            false,
+            Clock::default().epoch,
+        );
+
+        let mut system_lamports = 0;
+        let mut system_data = vec![0; 0];
+        let system_program = AccountInfo::new(
+            &system_program_id,
+            false,
+            false,
+            &mut system_lamports,
+            &mut system_data,
+            &system_program_id,
+            true,
+            Clock::default().epoch,
+        );
+
+        let accounts = vec![program_state, payer, system_program];
+
+        assert_eq!(
+            Processor::process_initialize(&program_id, &accounts, payer_pubkey),
+            Err(GuardError::InvalidAccountData.into())
+        );
+    }
+
+    #[test]
+    fn test_update_network_stats_requires_authority() {
+        setup();
+        let program_id = Pubkey::new_unique();
+        let authority_pubkey = Pubkey::new_unique();
+        let (state_pubkey, bump) = ProgramState::find_address(&program_id);
+
+        let mut state_lamports = 0;
+        let mut state_data = borsh::to_vec(&ProgramState::new(authority_pubkey, bump, 0)).unwrap();
+        let program_state = AccountInfo::new(
+            &state_pubkey,
+            false,
+            true,
+            &mut state_lamports,
+            &mut state_data,
+            &program_id,
+            false,
+            Clock::default().epoch,
+        );
+
+        let mut authority_lamports = 0;
+        let mut authority_data = vec![0; 0];
+        let authority = AccountInfo::new(
+            &authority_pubkey,
+            true,
+            false,
+            &mut authority_lamports,
+            &mut authority_data,
+            &program_id,
+            false,
+            Clock::default().epoch,
+        );
+
+        let mut impostor_lamports = 0;
+        let mut impostor_data = vec![0; 0];
+        let impostor_pubkey = Pubkey::new_unique();
+        let impostor = AccountInfo::new(
+            &impostor_pubkey,
+            true,
+            false,
+            &mut impostor_lamports,
+            &mut impostor_data,
+            &program_id,
+            false,
+            Clock::default().epoch,
+        );
+
+        let instruction = borsh::to_vec(&GuardInstruction::UpdateNetworkStats {
+            transactions_per_second: 4000,
+            average_block_time: 400,
+        })
+        .unwrap();
+
+        // A signer that is not the stored authority is rejected
+        let accounts = vec![program_state.clone(), impostor];
+        assert_eq!(
+            Processor::process(&program_id, &accounts, &instruction),
+            Err(GuardError::UnauthorizedAccount.into())
+        );
+
+        // The stored authority must also sign
+        let mut unsigned_authority = authority.clone();
+        unsigned_authority.is_signer = false;
+        let accounts = vec![program_state.clone(), unsigned_authority];
+        assert_eq!(
+            Processor::process(&program_id, &accounts, &instruction),
+            Err(GuardError::UnauthorizedAccount.into())
+        );
+
+        let accounts = vec![program_state, authority];
+        assert!(Processor::process(&program_id, &accounts, &instruction).is_ok());
+
+        let state = ProgramState::deserialize(&mut &accounts[0].data.borrow()[..]).unwrap();
+        assert_eq!(state.transactions_per_second, 4000);
+        assert_eq!(state.average_block_time, 400);
+        assert_eq!(state.last_updated, TEST_TIMESTAMP);
+    }
+
+    #[test]
+    fn test_update_network_stats_rejects_non_canonical_state() {
+        setup();
+        let program_id = Pubkey::new_unique();
+        let authority_pubkey = Pubkey::new_unique();
+        let (_, bump) = ProgramState::find_address(&program_id);
+
+        // A program-owned account holding a forged state naming the caller as authority
+        let forged_pubkey = Pubkey::new_unique();
+        let mut forged_lamports = 0;
+        let mut forged_data = borsh::to_vec(&ProgramState::new(authority_pubkey, bump, 0)).unwrap();
+        let forged_state = AccountInfo::new(
+            &forged_pubkey,
+            false,
+            true,
+            &mut forged_lamports,
+            &mut forged_data,
+            &program_id,
+            false,
+            Clock::default().epoch,
+        );
+
+        let mut authority_lamports = 0;
+        let mut authority_data = vec![0; 0];
+        let authority = AccountInfo::new(
+            &authority_pubkey,
+            true,
+            false,
+            &mut authority_lamports,
+            &mut authority_data,
+            &program_id,
+            false,
+            Clock::default().epoch,
+        );
+
+        let accounts = vec![forged_state, authority];
+        assert_eq!(
+            Processor::process_update_network_stats(&program_id, &accounts, 1, 1),
+            Err(GuardError::InvalidAccountData.into())
+        );
+    }
+
+    #[test]
+    fn test_authority_rotation() {
+        setup();
+        let program_id = Pubkey::new_unique();
+        let authority_pubkey = Pubkey::new_unique();
+        let new_authority_pubkey = Pubkey::new_unique();
+        let (state_pubkey, bump) = ProgramState::find_address(&program_id);
+
+        let mut state_lamports = 0;
+        let mut state_data = vec![0; ProgramState::SPACE];
+        ProgramState::new(authority_pubkey, bump, 0)
+            .serialize(&mut &mut state_data[..])
+            .unwrap();
+        let program_state = AccountInfo::new(
+            &state_pubkey,
+            false,
+            true,
+            &mut state_lamports,
+            &mut state_data,
+            &program_id,
+            false,
+            Clock::default().epoch,
+        );
+
+        let mut authority_lamports = 0;
+        let mut authority_data = vec![0; 0];
+        let authority = AccountInfo::new(
+            &authority_pubkey,
+            true,
+            false,
+            &mut authority_lamports,
+            &mut authority_data,
+            &program_id,
+            false,
+            Clock::default().epoch,
+        );
+
+        let mut new_authority_lamports = 0;
+        let mut new_authority_data = vec![0; 0];
+        let new_authority = AccountInfo::new(
+            &new_authority_pubkey,
+            true,
+            false,
+            &mut new_authority_lamports,
+            &mut new_authority_data,
+            &program_id,
+            false,
+            Clock::default().epoch,
+        );
+
+        let propose = borsh::to_vec(&GuardInstruction::ProposeAuthority {
+            new_authority: new_authority_pubkey,
+        })
+        .unwrap();
+        let accept = borsh::to_vec(&GuardInstruction::AcceptAuthority).unwrap();
+        let renounce = borsh::to_vec(&GuardInstruction::RenounceAuthority).unwrap();
+
+        // Only the current authority may propose
+        let accounts = vec![program_state.clone(), new_authority.clone()];
+        assert_eq!(
+            Processor::process(&program_id, &accounts, &propose),
+            Err(GuardError::UnauthorizedAccount.into())
+        );
+
+        let accounts = vec![program_state.clone(), authority.clone()];
+        assert!(Processor::process(&program_id, &accounts, &propose).is_ok());
+
+        // Only the nominee may accept
+        assert_eq!(
+            Processor::process(&program_id, &accounts, &accept),
+            Err(GuardError::UnauthorizedAccount.into())
+        );
+
+        let accounts = vec![program_state.clone(), new_authority.clone()];
+        assert!(Processor::process(&program_id, &accounts, &accept).is_ok());
+
+        let state = ProgramState::deserialize(&mut &accounts[0].data.borrow()[..]).unwrap();
+        assert_eq!(state.authority, new_authority_pubkey);
+        assert_eq!(state.pending_authority, None);
+
+        // The previous authority has lost its privileges
+        let accounts = vec![program_state.clone(), authority];
+        assert_eq!(
+            Processor::process(&program_id, &accounts, &renounce),
+            Err(GuardError::UnauthorizedAccount.into())
+        );
+
+        let accounts = vec![program_state, new_authority];
+        assert!(Processor::process(&program_id, &accounts, &renounce).is_ok());
+        assert_eq!(
+            Processor::process(&program_id, &accounts, &propose),
+            Err(GuardError::UnauthorizedAccount.into())
+        );
+    }
+
+    #[test]
+    fn test_analyze_contract() {
+        setup();
+        let program_id = Pubkey::new_unique();
+        let mut lamports = 0;
+        // mov64 r0, 0; call sol_log_; exit
+        let mut program_data = ElfBuilder::new(
+            &[slot(0xb7, 0, 0, 0, 0), slot(0x85, 0, 1, 0, -1), slot(0x95, 0, 0, 0, 0)].concat(),
+        )
+        .syscall(1, "sol_log_")
+        .build();
+        let mut state_data = vec![0; 1000];
+        
+        let target_program_pubkey = Pubkey::new_unique();
+        let target_program = AccountInfo::new(
+            &target_program_pubkey,
+            false,
+            true,
+            &mut lamports,
+            &mut program_data,
+            &bpf_loader::ID,
+            true,
+            Clock::default().epoch,
+        );
+
+        let mut analysis_state_lamports = 0;
+        let analysis_state_pubkey = Pubkey::new_unique();
+        let analysis_state = AccountInfo::new(
+            &analysis_state_pubkey,
+            false,
+            true,
+            &mut analysis_state_lamports,
+            &mut state_data,
+            &program_id,
+            false,
+            Clock::default().epoch,
+        );
+
+        let mut authority_lamports = 0;
+        let mut authority_data = vec![0; 0];
+        let authority_pubkey = Pubkey::new_unique();
+        let authority = AccountInfo::new(
+            &authority_pubkey,
+            true,
+            true,
+            &mut authority_lamports,
+            &mut authority_data,
+            &program_id,
+            false,
+            Clock::default().epoch,
+        );
+
+        let mut program_state_lamports = 0;
+        let (program_state_pubkey, bump) = ProgramState::find_address(&program_id);
+        let mut program_state_data = borsh::to_vec(&ProgramState::new(authority_pubkey, bump, 0)).unwrap();
+        let program_state = AccountInfo::new(
+            &program_state_pubkey,
+            false,
+            true,
+            &mut program_state_lamports,
+            &mut program_state_data,
+            &program_id,
+            false,
+            Clock::default().epoch,
+        );
+
+        let accounts = vec![
+            target_program,
+            analysis_state,
+            authority,
+            program_state,
+        ];
+
+        // Only programs deployed by a loader are analyzed, not data accounts
+        // holding a copy of their code
+        let mut data_account = accounts.clone();
+        data_account[0].owner = &program_id;
+        assert_eq!(
+            Processor::process_analyze_contract(&program_id, &data_account, 100),
+            Err(GuardError::InvalidTargetProgram.into())
+        );
+        data_account[0].owner = &bpf_loader_deprecated::ID;
+        data_account[0].executable = false;
+        assert_eq!(
+            Processor::process_analyze_contract(&program_id, &data_account, 100),
+            Err(GuardError::InvalidTargetProgram.into())
+        );
+        data_account[0].executable = true;
+        assert!(Processor::process_analyze_contract(&program_id, &data_account, 100).is_ok());
+
+        let result = Processor::process_analyze_contract(
+            &program_id,
+            &accounts,
+            100,
+        );
+
+        assert!(result.is_ok());
+        let state = SecurityAnalysisState::deserialize(&mut &accounts[1].data.borrow()[..]).unwrap();
+        assert_eq!(state.syscalls.len(), 1);
+        assert_eq!(state.syscalls[0].name(), Some("sol_log_"));
+        assert_eq!(state.syscalls[0].call_sites, 1);
+        assert!(state.capabilities().contains(Capabilities::LOGGING));
+        assert!(!state.capabilities().contains(Capabilities::CPI));
+
+        // The account only takes analyses of the program it was created for
+        let other_target_pubkey = Pubkey::new_unique();
+        let mut other_target = accounts[0].clone();
+        other_target.key = &other_target_pubkey;
+        let accounts = vec![other_target, accounts[1].clone(), accounts[2].clone(), accounts[3].clone()];
+        assert_eq!(
+            Processor::process_analyze_contract(&program_id, &accounts, 100),
+            Err(GuardError::InvalidAccountData.into())
+        );
+        let state = SecurityAnalysisState::deserialize(&mut &accounts[1].data.borrow()[..]).unwrap();
+        assert_eq!(state.target_program, target_program_pubkey);
+    }
+
+    #[test]
+    fn test_analyze_upgradeable_program() {
+        setup();
+        let program_id = Pubkey::new_unique();
+        let loader_id = bpf_loader_upgradeable::id();
+        let upgrade_authority = Pubkey::new_unique();
+
+        let programdata_pubkey = Pubkey::new_unique();
+        let mut programdata_lamports = 0;
+        let elf = ElfBuilder::new(&slot(0x95, 0, 0, 0, 0)).build();
+        let mut programdata_data = programdata_account(1234, Some(upgrade_authority), &elf);
+        let programdata = AccountInfo::new(
+            &programdata_pubkey,
+            false,
+            false,
+            &mut programdata_lamports,
+            &mut programdata_data,
+            &loader_id,
+            false,
+            Clock::default().epoch,
+        );
+
+        let target_program_pubkey = Pubkey::new_unique();
+        let mut target_lamports = 0;
+        let mut target_data = program_account(&programdata_pubkey);
+        let target_program = AccountInfo::new(
+            &target_program_pubkey,
+            false,
+            false,
+            &mut target_lamports,
+            &mut target_data,
+            &loader_id,
+            true,
+            Clock::default().epoch,
+        );
+
+        let analysis_state_pubkey = Pubkey::new_unique();
+        let mut analysis_state_lamports = 0;
+        let mut analysis_state_data = vec![0; SecurityAnalysisState::SPACE];
+        let analysis_state = AccountInfo::new(
+            &analysis_state_pubkey,
+            false,
+            true,
+            &mut analysis_state_lamports,
+            &mut analysis_state_data,
+            &program_id,
+            false,
+            Clock::default().epoch,
+        );
+
+        let authority_pubkey = Pubkey::new_unique();
+        let mut authority_lamports = 0;
+        let mut authority_data = vec![0; 0];
+        let authority = AccountInfo::new(
+            &authority_pubkey,
+            true,
+            false,
+            &mut authority_lamports,
+            &mut authority_data,
+            &program_id,
+            false,
+            Clock::default().epoch,
+        );
+
+        let (program_state_pubkey, bump) = ProgramState::find_address(&program_id);
+        let mut program_state_lamports = 0;
+        let mut program_state_data = borsh::to_vec(&ProgramState::new(authority_pubkey, bump, 0)).unwrap();
+        let program_state = AccountInfo::new(
+            &program_state_pubkey,
+            false,
+            false,
+            &mut program_state_lamports,
+            &mut program_state_data,
+            &program_id,
+            false,
+            Clock::default().epoch,
+        );
+
+        // The ProgramData account is required
+        let accounts = vec![
+            target_program.clone(),
+            analysis_state.clone(),
+            authority.clone(),
+            program_state.clone(),
+        ];
+        assert_eq!(
+            Processor::process_analyze_contract(&program_id, &accounts, 0),
+            Err(GuardError::InvalidTargetProgram.into())
+        );
+
+        // ...and must be the one the program account points to
+        let mut wrong_programdata = programdata.clone();
+        let wrong_programdata_pubkey = Pubkey::new_unique();
+        wrong_programdata.key = &wrong_programdata_pubkey;
+        let accounts = vec![
+            target_program.clone(),
+            analysis_state.clone(),
+            authority.clone(),
+            program_state.clone(),
+            wrong_programdata,
+        ];
+        assert_eq!(
+            Processor::process_analyze_contract(&program_id, &accounts, 0),
+            Err(GuardError::InvalidTargetProgram.into())
+        );
+
+        let accounts = vec![target_program, analysis_state, authority, program_state, programdata];
+        assert!(Processor::process_analyze_contract(&program_id, &accounts, 0).is_ok());
+
+        let state = SecurityAnalysisState::deserialize(&mut &accounts[1].data.borrow()[..]).unwrap();
+        assert_eq!(state.last_deploy_slot, 1234);
+        assert_eq!(state.upgrade_authority, Some(upgrade_authority));
+
+        // A live single-key upgrade authority is the only finding
+        assert_eq!(state.vulnerability_count, 1);
+        assert_eq!(state.safety_score, SafetyScore::new(80));
+        assert!(state.passed);
+        assert_eq!(
+            state.findings,
+            vec![Finding::new(DetectorId::UpgradeAuthority, Severity::High)]
+        );
+    }
+
+    #[test]
+    fn test_update_risk_model() {
+        setup();
+        let program_id = Pubkey::new_unique();
+        let loader_id = bpf_loader_upgradeable::id();
+        let authority_pubkey = Pubkey::new_unique();
+        let (state_pubkey, bump) = ProgramState::find_address(&program_id);
+
+        let mut state_lamports = 0;
+        let mut state_data = borsh::to_vec(&ProgramState::new(authority_pubkey, bump, 0)).unwrap();
+        let program_state = AccountInfo::new(
+            &state_pubkey,
+            false,
+            true,
+            &mut state_lamports,
+            &mut state_data,
+            &program_id,
+            false,
+            Clock::default().epoch,
+        );
+
+        let mut authority_lamports = 0;
+        let mut authority_data = vec![0; 0];
+        let authority = AccountInfo::new(
+            &authority_pubkey,
+            true,
+            false,
+            &mut authority_lamports,
+            &mut authority_data,
+            &program_id,
+            false,
+            Clock::default().epoch,
+        );
+
+        let mut impostor_lamports = 0;
+        let mut impostor_data = vec![0; 0];
+        let impostor_pubkey = Pubkey::new_unique();
+        let impostor = AccountInfo::new(
+            &impostor_pubkey,
+            true,
+            false,
+            &mut impostor_lamports,
+            &mut impostor_data,
+            &program_id,
+            false,
+            Clock::default().epoch,
+        );
+
+        let strict_model = RiskModel {
+            pass_threshold: 90,
+            ..RiskModel::default()
+        };
+        let update = borsh::to_vec(&GuardInstruction::UpdateRiskModel {
+            risk_model: strict_model.clone(),
+        })
+        .unwrap();
+
+        // Only the authority may change the model
+        let accounts = vec![program_state.clone(), impostor];
+        assert_eq!(
+            Processor::process(&program_id, &accounts, &update),
+            Err(GuardError::UnauthorizedAccount.into())
+        );
+
+        // Inconsistent bounds are rejected
+        let invalid = borsh::to_vec(&GuardInstruction::UpdateRiskModel {
+            risk_model: RiskModel {
+                pass_threshold: 101,
+                ..RiskModel::default()
+            },
+        })
+        .unwrap();
+        let accounts = vec![program_state.clone(), authority.clone()];
+        assert_eq!(
+            Processor::process(&program_id, &accounts, &invalid),
+            Err(GuardError::InvalidInstructionData.into())
+        );
+
+        assert!(Processor::process(&program_id, &accounts, &update).is_ok());
+        let state = ProgramState::deserialize(&mut &accounts[0].data.borrow()[..]).unwrap();
+        assert_eq!(state.risk_model, strict_model);
+
+        // A single-key upgradeable program now fails the stricter threshold
+        let programdata_pubkey = Pubkey::new_unique();
+        let mut programdata_lamports = 0;
+        let elf = ElfBuilder::new(&slot(0x95, 0, 0, 0, 0)).build();
+        let mut programdata_data = programdata_account(1, Some(Pubkey::new_unique()), &elf);
+        let programdata = AccountInfo::new(
+            &programdata_pubkey,
+            false,
+            false,
+            &mut programdata_lamports,
+            &mut programdata_data,
+            &loader_id,
+            false,
+            Clock::default().epoch,
+        );
+
+        let target_program_pubkey = Pubkey::new_unique();
+        let mut target_lamports = 0;
+        let mut target_data = program_account(&programdata_pubkey);
+        let target_program = AccountInfo::new(
+            &target_program_pubkey,
+            false,
+            false,
+            &mut target_lamports,
+            &mut target_data,
+            &loader_id,
+            true,
+            Clock::default().epoch,
+        );
+
+        let analysis_state_pubkey = Pubkey::new_unique();
+        let mut analysis_state_lamports = 0;
+        let mut analysis_state_data = vec![0; SecurityAnalysisState::SPACE];
+        let analysis_state = AccountInfo::new(
+            &analysis_state_pubkey,
+            false,
+            true,
+            &mut analysis_state_lamports,
+            &mut analysis_state_data,
+            &program_id,
+            false,
+            Clock::default().epoch,
+        );
+
+        let accounts = vec![target_program, analysis_state, authority, program_state, programdata];
+        assert!(Processor::process_analyze_contract(&program_id, &accounts, 0).is_ok());
+
+        let state = SecurityAnalysisState::deserialize(&mut &accounts[1].data.borrow()[..]).unwrap();
+        assert_eq!(state.safety_score, SafetyScore::new(80));
+        assert!(!state.passed);
+    }
+
+    #[test]
+    fn test_migrate_account() {
+        setup();
+        let program_id = Pubkey::new_unique();
+        let (state_pubkey, _) = ProgramState::find_address(&program_id);
+        let metrics_pubkey = Pubkey::new_unique();
+        let analysis_pubkey = Pubkey::new_unique();
+        let payer_pubkey = Pubkey::new_unique();
+        let rent = Rent::default();
+
+        // v1 accounts hold the baseline fields alone, sized to fit rather
+        // than for a full history
+        let state_v1 = borsh::to_vec(&LegacyProgramState {
+            authority: Pubkey::new_unique(),
+            initialized_at: TEST_TIMESTAMP,
+            last_updated: TEST_TIMESTAMP,
+            transactions_per_second: 0,
+            average_block_time: 0,
+        })
+        .unwrap();
+        let metrics_v1 = borsh::to_vec(&LegacyMetricsState {
+            total_transactions: 0,
+            total_gas_used: 0,
+            avg_gas_used: 0,
+            success_rate: 0,
+            last_update: TEST_TIMESTAMP,
+            gas_history: Vec::new(),
+            peak_gas_used: 0,
+            error_count: 0,
+        })
+        .unwrap();
+        let analysis_result = AnalysisResult {
+            timestamp: TEST_TIMESTAMP,
+            safety_score: SafetyScore::new(60),
+            vulnerability_count: 2,
+            status: AnalysisStatus::Completed,
+        };
+        let analysis_v1 = borsh::to_vec(&LegacySecurityAnalysisState {
+            target_program: Pubkey::new_unique(),
+            last_analysis: TEST_TIMESTAMP,
+            risk_score: 60,
+            vulnerability_count: 2,
+            status: AnalysisStatus::Completed,
+            analysis_history: vec![analysis_result.clone()],
+            patterns_version: 1,
+        })
+        .unwrap();
+
+        let mut input = serialize_input(&[
+            (state_pubkey, false, program_id, rent.minimum_balance(state_v1.len()), state_v1.clone()),
+            (metrics_pubkey, false, program_id, rent.minimum_balance(metrics_v1.len()), metrics_v1),
+            (payer_pubkey, true, system_program::id(), 1_000_000_000, Vec::new()),
+            (system_program::id(), false, system_program::id(), 0, Vec::new()),
+            (analysis_pubkey, false, program_id, rent.minimum_balance(analysis_v1.len()), analysis_v1),
+        ]);
+        let (_, accounts, _) = unsafe { deserialize(input.as_mut_ptr() as *mut u8) };
+        let migrate = |account_type| borsh::to_vec(&GuardInstruction::MigrateAccount { account_type }).unwrap();
+        let state_accounts = vec![accounts[0].clone(), accounts[2].clone(), accounts[3].clone()];
+        let metrics_accounts = vec![accounts[1].clone(), accounts[2].clone(), accounts[3].clone()];
+        let analysis_accounts = vec![accounts[4].clone(), accounts[2].clone(), accounts[3].clone()];
+
+        // v1 accounts cannot be used before migrating
+        assert!(Processor::load_program_state(&program_id, &accounts[0]).is_err());
+
+        // Only the program state PDA holds program state
+        assert_eq!(
+            Processor::process(&program_id, &state_accounts, &migrate(AccountType::Metrics)),
+            Err(GuardError::InvalidAccountData.into())
+        );
+        assert_eq!(
+            Processor::process(&program_id, &metrics_accounts, &migrate(AccountType::ProgramState)),
+            Err(GuardError::InvalidAccountData.into())
+        );
+
+        // v1 program state is replaced by Initialize, and v1 metrics are not
+        // keyed by their target program
+        assert_eq!(
+            Processor::process(&program_id, &state_accounts, &migrate(AccountType::ProgramState)),
+            Err(GuardError::InvalidAccountData.into())
+        );
+        assert_eq!(
+            Processor::process(&program_id, &metrics_accounts, &migrate(AccountType::Metrics)),
+            Err(GuardError::InvalidAccountData.into())
+        );
+
+        let migrate_analysis = migrate(AccountType::SecurityAnalysis);
+        assert!(Processor::process(&program_id, &analysis_accounts, &migrate_analysis).is_ok());
+        assert_eq!(accounts[4].data_len(), SecurityAnalysisState::SPACE);
+        assert_eq!(accounts[4].lamports(), rent.minimum_balance(SecurityAnalysisState::SPACE));
+        let migrated = SecurityAnalysisState::load(&accounts[4].data.borrow()).unwrap();
+        assert_eq!(migrated.safety_score, SafetyScore::new(60));
+        let mut analysis_data = accounts[4].data.borrow_mut();
+        let history = SecurityAnalysisState::history(&mut analysis_data).unwrap();
+        assert_eq!(history.iter().collect::<Vec<_>>(), vec![analysis_result]);
+        drop(analysis_data);
+
+        // Accounts with a header keep their type
+        assert_eq!(
+            Processor::process(&program_id, &analysis_accounts, &migrate(AccountType::Metrics)),
+            Err(GuardError::InvalidAccountData.into())
+        );
+
+        // Migrating again changes nothing
+        let payer_lamports = accounts[2].lamports();
+        assert!(Processor::process(&program_id, &analysis_accounts, &migrate_analysis).is_ok());
+        assert_eq!(accounts[4].data_len(), SecurityAnalysisState::SPACE);
+        assert_eq!(accounts[2].lamports(), payer_lamports);
+    }
+
+    #[test]
+    fn test_resize_history() {
+        setup();
+        let program_id = Pubkey::new_unique();
+        let (state_pubkey, bump) = ProgramState::find_address(&program_id);
+        let authority_pubkey = Pubkey::new_unique();
+        let target_pubkey = Pubkey::new_unique();
+        let (metrics_pubkey, metrics_bump) = MetricsState::find_address(&target_pubkey, &program_id);
+        let rent = Rent::default();
+
+        let mut state_data = vec![0; ProgramState::SPACE];
+        ProgramState::new(authority_pubkey, bump, 0)
+            .serialize(&mut &mut state_data[..])
+            .unwrap();
+
+        // A history previously resized to four samples
+        let mut metrics = MetricsState::new(target_pubkey, metrics_bump, 0);
+        let mut metrics_data = vec![0; MetricsState::space(4)];
+        let mut history = MetricsState::init_history(&mut metrics_data).unwrap();
+        for gas_used in 1..=4 {
+            metrics.record_transaction(&mut history, gas_used, true, gas_used as i64).unwrap();
+        }
+        metrics.serialize(&mut &mut metrics_data[..]).unwrap();
+
+        let mut input = serialize_input(&[
+            (metrics_pubkey, false, program_id, rent.minimum_balance(metrics_data.len()), metrics_data),
+            (authority_pubkey, true, system_program::id(), 1_000_000_000, Vec::new()),
+            (state_pubkey, false, program_id, rent.minimum_balance(ProgramState::SPACE), state_data),
+            (system_program::id(), false, system_program::id(), 0, Vec::new()),
+            (Pubkey::new_unique(), true, system_program::id(), 1_000_000_000, Vec::new()),
+            (target_pubkey, false, bpf_loader_upgradeable::id(), 1_000_000, Vec::new()),
+            (Pubkey::new_unique(), false, system_program::id(), 0, Vec::new()),
+        ]);
+        let (_, accounts, _) = unsafe { deserialize(input.as_mut_ptr() as *mut u8) };
+        let resize = |capacity| borsh::to_vec(&GuardInstruction::ResizeHistory { capacity }).unwrap();
+        let gas_history = |account: &AccountInfo| -> Vec<u64> {
+            let mut data = account.data.borrow_mut();
+            let history = MetricsState::history(&mut data).unwrap();
+            history.iter().map(|metric| metric.gas_used).collect()
+        };
+        let resize_accounts = vec![
+            accounts[0].clone(),
+            accounts[1].clone(),
+            accounts[2].clone(),
+            accounts[3].clone(),
+            accounts[6].clone(),
+        ];
+
+        // Only the program state authority may resize
+        let mut impostor_accounts = resize_accounts.clone();
+        impostor_accounts[1] = accounts[4].clone();
+        assert_eq!(
+            Processor::process(&program_id, &impostor_accounts, &resize(2)),
+            Err(GuardError::UnauthorizedAccount.into())
+        );
+        assert_eq!(
+            Processor::process(&program_id, &resize_accounts, &resize(0)),
+            Err(GuardError::InvalidInstructionData.into())
+        );
+        let mut state_accounts = resize_accounts.clone();
+        state_accounts[0] = accounts[2].clone();
+        assert_eq!(
+            Processor::process(&program_id, &state_accounts, &resize(2)),
+            Err(GuardError::InvalidAccountData.into())
+        );
+
+        // Shrinking keeps the latest samples and refunds the freed rent to
+        // the recipient
+        let authority_lamports = accounts[1].lamports();
+        let metrics_lamports = accounts[0].lamports();
+        assert!(Processor::process(&program_id, &resize_accounts, &resize(2)).is_ok());
+        assert_eq!(accounts[0].data_len(), MetricsState::space(2));
+        assert_eq!(accounts[0].lamports(), rent.minimum_balance(MetricsState::space(2)));
+        assert_eq!(accounts[6].lamports(), metrics_lamports - accounts[0].lamports());
+        assert_eq!(accounts[1].lamports(), authority_lamports);
+        assert_eq!(gas_history(&accounts[0]), vec![3, 4]);
+
+        // Growing keeps every sample and charges the authority
+        let shrunk_lamports = accounts[0].lamports();
+        assert!(Processor::process(&program_id, &resize_accounts, &resize(6)).is_ok());
+        assert_eq!(accounts[0].data_len(), MetricsState::space(6));
+        assert_eq!(accounts[0].lamports(), rent.minimum_balance(MetricsState::space(6)));
+        assert_eq!(
+            accounts[1].lamports(),
+            authority_lamports + shrunk_lamports - accounts[0].lamports()
+        );
+        assert_eq!(gas_history(&accounts[0]), vec![3, 4]);
+        let loaded = MetricsState::load(&accounts[0].data.borrow()).unwrap();
+        assert_eq!(loaded.total_transactions, 4);
+
+        // The resized history fills up to its new capacity
+        let record_accounts = vec![accounts[0].clone(), accounts[1].clone(), accounts[5].clone()];
+        for gas_used in 5..=10 {
+            let record = GuardInstruction::RecordMetrics { gas_used, success: true };
+            assert!(Processor::process(&program_id, &record_accounts, &borsh::to_vec(&record).unwrap()).is_ok());
+        }
+        assert_eq!(gas_history(&accounts[0]), vec![5, 6, 7, 8, 9, 10]);
+    }
+
+    #[test]
+    fn test_record_metrics() {
+        setup();
+        let program_id = Pubkey::new_unique();
+        let target_pubkey = Pubkey::new_unique();
+        let (metrics_pubkey, bump) = MetricsState::find_address(&target_pubkey, &program_id);
+        let rent = Rent::default();
+
+        let mut input = serialize_input(&[
+            (metrics_pubkey, false, system_program::id(), 0, Vec::new()),
+            (Pubkey::new_unique(), true, system_program::id(), 1_000_000_000, Vec::new()),
+            (target_pubkey, false, bpf_loader_upgradeable::id(), 1_000_000, Vec::new()),
+            (system_program::id(), false, system_program::id(), 0, Vec::new()),
+            (Pubkey::new_unique(), false, bpf_loader_upgradeable::id(), 1_000_000, Vec::new()),
+            (Pubkey::new_unique(), false, system_program::id(), 0, Vec::new()),
+        ]);
+        let (_, accounts, _) = unsafe { deserialize(input.as_mut_ptr() as *mut u8) };
+        let initialize = |history_capacity| {
+            borsh::to_vec(&GuardInstruction::InitializeMetrics { history_capacity }).unwrap()
+        };
+        let record = |gas_used| borsh::to_vec(&GuardInstruction::RecordMetrics { gas_used, success: true }).unwrap();
+        let init_accounts = vec![accounts[0].clone(), accounts[1].clone(), accounts[2].clone(), accounts[3].clone()];
+        let record_accounts = vec![accounts[0].clone(), accounts[1].clone(), accounts[2].clone()];
+        let other_target_accounts = vec![accounts[0].clone(), accounts[1].clone(), accounts[4].clone()];
+
+        // Metrics are recorded only once created
+        assert_eq!(
+            Processor::process(&program_id, &record_accounts, &record(1000)),
+            Err(GuardError::InvalidAccountData.into())
+        );
+
+        // and only at the PDA of their target
+        let other_init_accounts =
+            vec![accounts[0].clone(), accounts[1].clone(), accounts[4].clone(), accounts[3].clone()];
+        assert_eq!(
+            Processor::process(&program_id, &other_init_accounts, &initialize(10)),
+            Err(GuardError::InvalidAccountData.into())
+        );
+        let stray_accounts = vec![accounts[5].clone(), accounts[1].clone(), accounts[2].clone(), accounts[3].clone()];
+        assert_eq!(
+            Processor::process(&program_id, &stray_accounts, &initialize(10)),
+            Err(GuardError::InvalidAccountData.into())
+        );
+        assert_eq!(
+            Processor::process(&program_id, &init_accounts, &initialize(0)),
+            Err(GuardError::InvalidInstructionData.into())
+        );
+
+        // The creator picks the history capacity and pays for it
+        assert!(Processor::process(&program_id, &init_accounts, &initialize(10)).is_ok());
+        assert_eq!(accounts[0].owner, &program_id);
+        assert_eq!(accounts[0].data_len(), MetricsState::space(10));
+        assert_eq!(accounts[0].lamports(), rent.minimum_balance(MetricsState::space(10)));
+        assert_eq!(accounts[1].lamports(), 1_000_000_000 - accounts[0].lamports());
+        assert_eq!(
+            Processor::process(&program_id, &init_accounts, &initialize(10)),
+            Err(GuardError::InitializationFailed.into())
+        );
+
+        let mut unsigned_accounts = record_accounts.clone();
+        unsigned_accounts[1].is_signer = false;
+        assert_eq!(
+            Processor::process(&program_id, &unsigned_accounts, &record(1000)),
+            Err(GuardError::UnauthorizedAccount.into())
+        );
+
+        assert!(Processor::process(&program_id, &record_accounts, &record(1000)).is_ok());
+        assert!(Processor::process(&program_id, &record_accounts, &record(3000)).is_ok());
+        let metrics = MetricsState::load(&accounts[0].data.borrow()).unwrap();
+        assert_eq!(metrics.target_program, target_pubkey);
+        assert_eq!(metrics.bump, bump);
+        assert_eq!(metrics.total_transactions, 2);
+        assert_eq!(metrics.last_update, TEST_TIMESTAMP);
+        let mut metrics_data = accounts[0].data.borrow_mut();
+        let history = MetricsState::history(&mut metrics_data).unwrap();
+        assert_eq!(history.capacity(), 10);
+        assert_eq!(history.gas_trend(), Some(2000));
+        drop(metrics_data);
+
+        // The account only takes records for its target
+        assert_eq!(
+            Processor::process(&program_id, &other_target_accounts, &record(1000)),
+            Err(GuardError::InvalidAccountData.into())
+        );
+    }
+
+    #[test]
+    fn test_migrate_metrics() {
+        setup();
+        let program_id = Pubkey::new_unique();
+        let (state_pubkey, bump) = ProgramState::find_address(&program_id);
+        let authority_pubkey = Pubkey::new_unique();
+        let target_pubkey = Pubkey::new_unique();
+        let (metrics_pubkey, metrics_bump) = MetricsState::find_address(&target_pubkey, &program_id);
+        let rent = Rent::default();
+
+        let mut state_data = vec![0; ProgramState::SPACE];
+        ProgramState::new(authority_pubkey, bump, 0)
+            .serialize(&mut &mut state_data[..])
+            .unwrap();
+
+        // A v1 account holding three samples inline
+        let gas_history: Vec<GasMetric> = (1..=3)
+            .map(|gas_used| GasMetric { timestamp: gas_used as i64, gas_used, success: true })
+            .collect();
+        let legacy_data = borsh::to_vec(&LegacyMetricsState {
+            total_transactions: 3,
+            total_gas_used: 6,
+            avg_gas_used: 2,
+            success_rate: 100,
+            last_update: 3,
+            gas_history: gas_history.clone(),
+            peak_gas_used: 3,
+            error_count: 0,
+        })
+        .unwrap();
+        let legacy_lamports = rent.minimum_balance(legacy_data.len());
+
+        let mut input = serialize_input(&[
+            (Pubkey::new_unique(), false, program_id, legacy_lamports, legacy_data),
+            (metrics_pubkey, false, system_program::id(), 0, Vec::new()),
+            (authority_pubkey, true, system_program::id(), 1_000_000_000, Vec::new()),
+            (state_pubkey, false, program_id, rent.minimum_balance(ProgramState::SPACE), state_data),
+            (target_pubkey, false, bpf_loader_upgradeable::id(), 1_000_000, Vec::new()),
+            (system_program::id(), false, system_program::id(), 0, Vec::new()),
+            (Pubkey::new_unique(), true, system_program::id(), 1_000_000_000, Vec::new()),
+        ]);
+        let (_, accounts, _) = unsafe { deserialize(input.as_mut_ptr() as *mut u8) };
+        let migrate = |history_capacity| {
+            borsh::to_vec(&GuardInstruction::MigrateMetrics { history_capacity }).unwrap()
+        };
+        let migrate_accounts: Vec<_> = accounts[..6].to_vec();
+
+        // Only the program state authority may name the target
+        let mut impostor_accounts = migrate_accounts.clone();
+        impostor_accounts[2] = accounts[6].clone();
+        assert_eq!(
+            Processor::process(&program_id, &impostor_accounts, &migrate(2)),
+            Err(GuardError::UnauthorizedAccount.into())
+        );
+
+        // The program state never holds metrics
+        let mut state_accounts = migrate_accounts.clone();
+        state_accounts[0] = accounts[3].clone();
+        assert_eq!(
+            Processor::process(&program_id, &state_accounts, &migrate(2)),
+            Err(GuardError::InvalidAccountData.into())
+        );
+
+        // The metrics move to the PDA of the target
+        let mut stray_accounts = migrate_accounts.clone();
+        stray_accounts[1] = accounts[6].clone();
+        assert_eq!(
+            Processor::process(&program_id, &stray_accounts, &migrate(2)),
+            Err(GuardError::InvalidAccountData.into())
+        );
+
+        // keeping the most recent samples, funded by the old account first
+        let authority_lamports = accounts[2].lamports();
+        assert!(Processor::process(&program_id, &migrate_accounts, &migrate(2)).is_ok());
+        assert_eq!(accounts[1].owner, &program_id);
+        assert_eq!(accounts[1].data_len(), MetricsState::space(2));
+        assert_eq!(accounts[1].lamports(), rent.minimum_balance(MetricsState::space(2)));
+        assert_eq!(
+            accounts[2].lamports(),
+            authority_lamports + legacy_lamports - accounts[1].lamports()
+        );
+        let metrics = Processor::load_metrics_state(&program_id, &accounts[1], &target_pubkey).unwrap();
+        assert_eq!(metrics.bump, metrics_bump);
+        assert_eq!(metrics.total_transactions, 3);
+        assert_eq!(metrics.peak_gas_used, 3);
+        let mut metrics_data = accounts[1].data.borrow_mut();
+        let history = MetricsState::history(&mut metrics_data).unwrap();
+        assert_eq!(history.iter().collect::<Vec<_>>(), gas_history[1..]);
+        drop(metrics_data);
+
+        // The old account is closed
+        assert_eq!(accounts[0].lamports(), 0);
+        assert!(accounts[0].data.borrow().iter().all(|&byte| byte == 0));
+
+        // and the new one is never overwritten
+        assert_eq!(
+            Processor::process(&program_id, &migrate_accounts, &migrate(2)),
+            Err(GuardError::InitializationFailed.into())
+        );
+    }
+
+    #[test]
+    fn test_classify_upgrade_authority() {
+        let multisig_pubkey = Pubkey::new_unique();
+        let (vault, _) = Pubkey::find_program_address(
+            &[b"multisig", multisig_pubkey.as_ref(), b"vault", &[0]],
+            &SQUADS_V4_PROGRAM_ID,
+        );
+        let mut lamports = 0;
+        let mut data = vec![0; 0];
+        let multisig_account = AccountInfo::new(
+            &multisig_pubkey,
+            false,
+            false,
+            &mut lamports,
+            &mut data,
+            &SQUADS_V4_PROGRAM_ID,
+            false,
+            Clock::default().epoch,
+        );
+
+        assert_eq!(
+            Processor::classify_upgrade_authority(Some(vault), None),
+            UpgradeAuthorityKind::SingleKey
+        );
+        assert_eq!(
+            Processor::classify_upgrade_authority(Some(vault), Some(&multisig_account)),
+            UpgradeAuthorityKind::Multisig
+        );
+        assert_eq!(
+            Processor::classify_upgrade_authority(None, Some(&multisig_account)),
+            UpgradeAuthorityKind::Immutable
+        );
+
+        // The authority must be a signer of the supplied multisig
+        assert_eq!(
+            Processor::classify_upgrade_authority(Some(Pubkey::new_unique()), Some(&multisig_account)),
+            UpgradeAuthorityKind::SingleKey
+        );
+        let mut other_multisig = multisig_account.clone();
+        let other_multisig_pubkey = Pubkey::new_unique();
+        other_multisig.key = &other_multisig_pubkey;
+        assert_eq!(
+            Processor::classify_upgrade_authority(Some(vault), Some(&other_multisig)),
+            UpgradeAuthorityKind::SingleKey
+        );
+    }
+
+    #[test]
+    fn test_suspicious_patterns() {
+        let cfg = |text| Cfg::new(text, SbpfVersion::V1, 0, [], Vec::new());
+        let safe_text = [
+            slot(0xb7, 0, 0, 0, 0), // mov64 r0, 0
+            slot(0x95, 0, 0, 0, 0), // exit
+        ]
+        .concat();
+        let unsafe_text = slot(0x05, 0, 0, -1, 0); // ja -1
+        let loop_text = [
+            slot(0x07, 1, 0, 0, 1),  // add64 r1, 1
+            slot(0x05, 0, 0, -2, 0), // ja -2
+        ]
+        .concat();
+        // x86 `jmp $` bytes are not an SBF self-loop
+        let x86_text = [0xeb, 0xfe, 0, 0, 0, 0, 0, 0];
+
+        assert!(Processor::check_suspicious_patterns(&cfg(&safe_text)).is_empty());
+        assert_eq!(Processor::check_suspicious_patterns(&cfg(&unsafe_text)), vec![0]);
+        assert_eq!(Processor::check_suspicious_patterns(&cfg(&loop_text)), vec![1]);
+        assert!(Processor::check_suspicious_patterns(&cfg(&x86_text)).is_empty());
+    }
+
+    #[test]
+    fn test_unreachable_code() {
+        let text = [
+            slot(0x95, 0, 0, 0, 0), // exit
+            slot(0xb7, 0, 0, 0, 0), // mov64 r0, 0
+            slot(0x95, 0, 0, 0, 0), // exit
+        ]
+        .concat();
+        let cfg = Cfg::new(&text, SbpfVersion::V1, 0, [], Vec::new());
+        assert_eq!(
+            Processor::check_unreachable_code(&cfg, &cfg.reachability()),
+            Some((1, 2))
+        );
+
+        // A second abort after an aborting call is padding, not dead code
+        let padded = slot(0x85, 0, 1, 0, -1).repeat(2);
+        let external_calls = vec![
+            ExternalCall { pc: 0, name: "abort" },
+            ExternalCall { pc: 1, name: "abort" },
+        ];
+        let cfg = Cfg::new(&padded, SbpfVersion::V1, 0, [], external_calls);
+        assert_eq!(Processor::check_unreachable_code(&cfg, &cfg.reachability()), None);
+    }
+
+    #[test]
+    fn test_missing_signer() {
+        let cfg = |text| Cfg::new(text, SbpfVersion::V1, 0, [], Vec::new());
+        let unchecked = [
+            slot(0x79, 2, 1, 0x50, 0), // ldxdw r2, [r1+0x50]   lamports
+            slot(0x07, 2, 0, 0, 1),    // add64 r2, 1
+            slot(0x7b, 1, 2, 0x50, 0), // stxdw [r1+0x50], r2
+            slot(0x7b, 1, 2, 0x50, 0), // stxdw [r1+0x50], r2
+            slot(0x95, 0, 0, 0, 0),    // exit
+        ]
+        .concat();
+        let accesses = input::analyze(&cfg(&unchecked)).accesses;
+        assert_eq!(Processor::check_missing_signer(&accesses), vec![(2, 0)]);
+
+        // Loading the flag without branching on it is not a check
+        let loaded = [
+            slot(0x71, 3, 1, 0x9, 0),  // ldxb r3, [r1+0x9]   is_signer
+            slot(0x7b, 1, 2, 0x50, 0), // stxdw [r1+0x50], r2
+            slot(0x95, 0, 0, 0, 0),    // exit
+        ]
+        .concat();
+        let accesses = input::analyze(&cfg(&loaded)).accesses;
+        assert_eq!(Processor::check_missing_signer(&accesses), vec![(1, 0)]);
+
+        let checked = [
+            slot(0x71, 3, 1, 0x9, 0),  // ldxb r3, [r1+0x9]   is_signer
+            slot(0x15, 3, 0, 1, 0),    // jeq r3, 0, +1
+            slot(0x7b, 1, 2, 0x50, 0), // stxdw [r1+0x50], r2
+            slot(0x95, 0, 0, 0, 0),    // exit
+        ]
+        .concat();
+        let accesses = input::analyze(&cfg(&checked)).accesses;
+        assert!(Processor::check_missing_signer(&accesses).is_empty());
+    }
+
+    #[test]
+    fn test_missing_owner() {
+        let cfg = |text| Cfg::new(text, SbpfVersion::V1, 0, [], Vec::new());
+        let unchecked = [
+            slot(0x79, 2, 1, 0x60, 0), // ldxdw r2, [r1+0x60]   account data
+            slot(0x95, 0, 0, 0, 0),    // exit
+        ]
+        .concat();
+        let accesses = input::analyze(&cfg(&unchecked)).accesses;
+        assert_eq!(Processor::check_missing_owner(&accesses), vec![(0, 0)]);
+
+        // Loading the owner, as deserializers do, is not a check
+        let loaded = [
+            slot(0x79, 3, 1, 0x30, 0), // ldxdw r3, [r1+0x30]   owner
+            slot(0x79, 2, 1, 0x60, 0), // ldxdw r2, [r1+0x60]
+            slot(0x95, 0, 0, 0, 0),    // exit
+        ]
+        .concat();
+        let accesses = input::analyze(&cfg(&loaded)).accesses;
+        assert_eq!(Processor::check_missing_owner(&accesses), vec![(1, 0)]);
+
+        let checked = [
+            slot(0x79, 3, 1, 0x30, 0), // ldxdw r3, [r1+0x30]   owner
+            slot(0x5d, 3, 4, 1, 0),    // jne r3, r4, +1
+            slot(0x79, 2, 1, 0x60, 0), // ldxdw r2, [r1+0x60]
+            slot(0x95, 0, 0, 0, 0),    // exit
+        ]
+        .concat();
+        let accesses = input::analyze(&cfg(&checked)).accesses;
+        assert!(Processor::check_missing_owner(&accesses).is_empty());
+    }
+
+    #[test]
+    fn test_type_cosplay() {
+        let cfg = |text| Cfg::new(text, SbpfVersion::V1, 0, [], Vec::new());
+        // The discriminator is read along with the rest, but never compared
+        let unchecked = [
+            slot(0x79, 2, 1, 0x60, 0), // ldxdw r2, [r1+0x60]   discriminator
+            slot(0x79, 3, 1, 0x68, 0), // ldxdw r3, [r1+0x68]   account data
+            slot(0x95, 0, 0, 0, 0),    // exit
+        ]
+        .concat();
+        let accesses = input::analyze(&cfg(&unchecked)).accesses;
+        assert_eq!(Processor::check_type_cosplay(&accesses), vec![(1, 0)]);
+
+        let checked = [
+            slot(0x79, 2, 1, 0x60, 0), // ldxdw r2, [r1+0x60]
+            slot(0x5d, 2, 4, 2, 0),    // jne r2, r4, +2
+            slot(0x79, 3, 1, 0x68, 0), // ldxdw r3, [r1+0x68]
+            slot(0x95, 0, 0, 0, 0),    // exit
+            slot(0x95, 0, 0, 0, 0),    // exit
+        ]
+        .concat();
+        let accesses = input::analyze(&cfg(&checked)).accesses;
+        assert!(Processor::check_type_cosplay(&accesses).is_empty());
+    }
+
+    #[test]
+    fn test_account_revival() {
+        let drain = |pc, function, data_wiped| LamportDrain {
+            pc,
+            function,
+            data_wiped,
+        };
+        let drains = [
+            drain(3, 0, false),
+            drain(5, 0, false),
+            drain(12, 10, true),
+        ];
+        assert_eq!(Processor::check_account_revival(&drains), vec![(3, 0)]);
+    }
+
+    #[test]
+    fn test_bump_canonicalization() {
+        let call = |pc, syscall, bump, bump_found| PdaCall {
+            pc,
+            function: 0,
+            syscall,
+            seeds: SeedsPattern::Dynamic,
+            bump,
+            bump_found,
+        };
+        let pda_calls = [
+            call(1, PdaSyscall::TryFind, None, false),
+            call(2, PdaSyscall::Create, Some(BumpSource::Constant), false),
+            // A supplied bump checked against the canonical one
+            call(3, PdaSyscall::Create, Some(BumpSource::CallerControlled), true),
+            call(4, PdaSyscall::Create, Some(BumpSource::CallerControlled), false),
+            // A canonical bump stored when the account was created
+            call(5, PdaSyscall::Create, Some(BumpSource::Stored), false),
+            call(6, PdaSyscall::Create, Some(BumpSource::Unknown), false),
+        ];
+        assert_eq!(Processor::check_bump_canonicalization(&pda_calls), vec![(4, 0)]);
+    }
+
+    #[test]
+    fn test_arbitrary_cpi() {
+        let mut compared = FieldSet::default();
+        compared.insert(AccountField::Key);
+        let invoke = |pc, program_id, compared| Invoke {
+            pc,
+            function: 0,
+            program_id,
+            compared,
+        };
+        let invokes = [
+            invoke(3, ProgramIdSource::Constant, FieldSet::default()),
+            invoke(5, ProgramIdSource::AccountKey, compared),
+            invoke(7, ProgramIdSource::AccountKey, FieldSet::default()),
+            invoke(9, ProgramIdSource::Unknown, FieldSet::default()),
+        ];
+
+        let findings = Processor::check_arbitrary_cpi(&invokes);
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[0].severity, Severity::Critical);
+        assert_eq!(findings[0].offset, Some(7));
+        assert_eq!(findings[1].severity, Severity::Info);
+        assert_eq!(findings[1].code, 1);
+        assert_eq!(findings[1].offset, Some(9));
+    }
+
+    #[test]
+    fn test_overflow_checks() {
+        let text = [
+            slot(0x0f, 1, 2, 0, 0), // add64 r1, r2
+            slot(0x95, 0, 0, 0, 0), // exit
+        ]
+        .concat();
+        let unchecked = ElfBuilder::new(&text).rodata(b"balance").build();
+        let checked = ElfBuilder::new(&text)
+            .rodata(b"attempt to add with overflow")
+            .build();
+        let no_arithmetic = ElfBuilder::new(&text[8..]).build();
+
+        for (bytes, expected) in [(unchecked, false), (checked, true), (no_arithmetic, true)] {
+            let elf = Elf::parse(&bytes).unwrap();
+            let cfg = Cfg::from_elf(&elf).unwrap();
+            assert_eq!(Processor::check_overflow_checks(&elf, &cfg), expected);
+        }
+    }
+
+    #[test]
+    fn test_resource_usage() {
+        let cost = |worst_case, unbounded| FunctionCost {
+            entry: 0,
+            worst_case,
+            unbounded,
+        };
+        assert!(Processor::check_resource_usage(&cost(150_000, false), 200_000).is_empty());
+
+        let over_budget = Processor::check_resource_usage(&cost(250_000, false), 200_000);
+        assert_eq!(over_budget.len(), 1);
+        assert_eq!(over_budget[0].severity, Severity::High);
+        assert_eq!(over_budget[0].function, Some(0));
+
+        let over_limit = Processor::check_resource_usage(&cost(1_500_000, true), 200_000);
+        assert_eq!(over_limit.len(), 2);
+        assert_eq!(over_limit[0].severity, Severity::Critical);
+        assert_eq!(over_limit[0].code, 2);
+        assert_eq!(over_limit[1].severity, Severity::Info);
+        assert_eq!(over_limit[1].code, 1);
+    }
+}
@@ -0,0 +1,191 @@
+// program/src/ring_buffer.rs
+//! Fixed-capacity histories kept in place in account data
+//!
+//! A ring takes `RingBuffer::space(capacity)` bytes: its capacity, the number
+//! of stored entries and the slot the next entry goes to, each a `u32`,
+//! followed by `capacity` slots of `T::MAX_SIZE` bytes. Entries are read and
+//! written one slot at a time, so pushing costs the same however long the
+//! history is, and overwrites the oldest entry once the ring is full.
+
+use std::marker::PhantomData;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{msg, program_error::ProgramError};
+
+use crate::{error::GuardError, state::MaxSize};
+
+const CAPACITY_OFFSET: usize = 0;
+const LEN_OFFSET: usize = 4;
+const HEAD_OFFSET: usize = 8;
+const SLOTS_OFFSET: usize = 12;
+
+/// Ring of fixed-size `T` entries over borrowed account bytes
+pub struct RingBuffer<'a, T> {
+    data: &'a mut [u8],
+    capacity: usize,
+    _entry: PhantomData<T>,
+}
+
+impl<'a, T: BorshSerialize + BorshDeserialize + MaxSize> RingBuffer<'a, T> {
+    /// Bytes taken by a ring of `capacity` entries
+    pub const fn space(capacity: usize) -> usize {
+        SLOTS_OFFSET + capacity * T::MAX_SIZE
+    }
+
+    /// Entries a ring taking `len` bytes has room for
+    pub const fn capacity_for(len: usize) -> usize {
+        len.saturating_sub(SLOTS_OFFSET) / T::MAX_SIZE
+    }
+
+    /// Ring previously laid out over `data` by `init`
+    pub fn new(data: &'a mut [u8]) -> Result<Self, ProgramError> {
+        if data.len() < SLOTS_OFFSET {
+            msg!("Account too small for its history");
+            return Err(GuardError::InvalidAccountData.into());
+        }
+        let capacity = read_u32(data, CAPACITY_OFFSET) as usize;
+        let ring = Self {
+            data,
+            capacity,
+            _entry: PhantomData,
+        };
+        if ring.data.len() < Self::space(capacity) || ring.len() > capacity || ring.head() >= capacity.max(1) {
+            msg!("Corrupted history");
+            return Err(GuardError::InvalidAccountData.into());
+        }
+        Ok(ring)
+    }
+
+    /// Lays out an empty ring of `capacity` entries over `data`
+    pub fn init(data: &'a mut [u8], capacity: usize) -> Result<Self, ProgramError> {
+        if data.len() < Self::space(capacity) {
+            msg!("Account too small for a history of {} entries", capacity);
+            return Err(GuardError::InsufficientBufferSize.into());
+        }
+        write_u32(data, CAPACITY_OFFSET, capacity as u32);
+        write_u32(data, LEN_OFFSET, 0);
+        write_u32(data, HEAD_OFFSET, 0);
+        Self::new(data)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        read_u32(self.data, LEN_OFFSET) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Entry `index` positions after the oldest one
+    pub fn get(&self, index: usize) -> Option<T> {
+        let len = self.len();
+        if index >= len {
+            return None;
+        }
+        let slot = (self.head() + self.capacity - len + index) % self.capacity;
+        T::deserialize(&mut &self.slot(slot)[..]).ok()
+    }
+
+    /// The most recent entry
+    pub fn last(&self) -> Option<T> {
+        self.get(self.len().checked_sub(1)?)
+    }
+
+    /// Entries from the oldest to the most recent
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        (0..self.len()).filter_map(|index| self.get(index))
+    }
+
+    /// Appends `entry`, evicting the oldest entry when the ring is full
+    pub fn push(&mut self, entry: &T) -> Result<(), ProgramError> {
+        if self.capacity == 0 {
+            return Ok(());
+        }
+        let head = self.head();
+        entry.serialize(&mut self.slot_mut(head))?;
+        write_u32(self.data, HEAD_OFFSET, ((head + 1) % self.capacity) as u32);
+        let len = (self.len() + 1).min(self.capacity);
+        write_u32(self.data, LEN_OFFSET, len as u32);
+        Ok(())
+    }
+
+    fn head(&self) -> usize {
+        read_u32(self.data, HEAD_OFFSET) as usize
+    }
+
+    fn slot(&self, slot: usize) -> &[u8] {
+        let start = SLOTS_OFFSET + slot * T::MAX_SIZE;
+        &self.data[start..start + T::MAX_SIZE]
+    }
+
+    fn slot_mut(&mut self, slot: usize) -> &mut [u8] {
+        let start = SLOTS_OFFSET + slot * T::MAX_SIZE;
+        &mut self.data[start..start + T::MAX_SIZE]
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn write_u32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_and_wrap() {
+        let mut data = vec![0; RingBuffer::<u64>::space(3)];
+        let mut ring = RingBuffer::<u64>::init(&mut data, 3).unwrap();
+        assert!(ring.is_empty());
+        assert_eq!(ring.last(), None);
+
+        ring.push(&1).unwrap();
+        ring.push(&2).unwrap();
+        assert_eq!(ring.iter().collect::<Vec<_>>(), vec![1, 2]);
+
+        // The oldest entries make way once the ring is full
+        for value in 3..=5 {
+            ring.push(&value).unwrap();
+        }
+        assert_eq!(ring.len(), 3);
+        assert_eq!(ring.iter().collect::<Vec<_>>(), vec![3, 4, 5]);
+        assert_eq!(ring.get(0), Some(3));
+        assert_eq!(ring.last(), Some(5));
+        assert_eq!(ring.get(3), None);
+
+        // The entries live in the account bytes
+        let ring = RingBuffer::<u64>::new(&mut data).unwrap();
+        assert_eq!(ring.capacity(), 3);
+        assert_eq!(ring.iter().collect::<Vec<_>>(), vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn test_invalid_layouts() {
+        let mut data = vec![0; RingBuffer::<u64>::space(2)];
+        assert!(RingBuffer::<u64>::init(&mut data, 3).is_err());
+        assert!(RingBuffer::<u64>::new(&mut data[..8]).is_err());
+        assert_eq!(RingBuffer::<u64>::capacity_for(data.len() + 7), 2);
+        assert_eq!(RingBuffer::<u64>::capacity_for(4), 0);
+
+        // A zero-capacity ring stores nothing
+        let mut ring = RingBuffer::<u64>::new(&mut data).unwrap();
+        ring.push(&1).unwrap();
+        assert!(ring.is_empty());
+
+        // Counters beyond the capacity or the account are rejected
+        write_u32(&mut data, CAPACITY_OFFSET, 2);
+        write_u32(&mut data, LEN_OFFSET, 3);
+        assert!(RingBuffer::<u64>::new(&mut data).is_err());
+        write_u32(&mut data, CAPACITY_OFFSET, 3);
+        write_u32(&mut data, LEN_OFFSET, 0);
+        assert!(RingBuffer::<u64>::new(&mut data).is_err());
+    }
+}
@@ -1,4 +1,6 @@
 // program/src/state.rs
+use std::cmp::Reverse;
+
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     program_error::ProgramError,
@@ -7,10 +9,101 @@ use solana_program::{
     msg,
 };
 
-use crate::error::GuardError;
+use crate::{
+    analysis::{
+        input::{PdaSyscall, SeedsPattern},
+        syscalls::{Capabilities, SyscallCount},
+    },
+    constants::{
+        ACCOUNT_LAYOUT_VERSION, DEFAULT_ANALYSIS_HISTORY, DEFAULT_COMPUTE_BUDGET, DEFAULT_GAS_HISTORY,
+        MAX_COMPUTE_UNIT_LIMIT, MAX_DETECTORS, MAX_STORED_FINDINGS, MAX_STORED_PDA_DERIVATIONS, MAX_STORED_SYSCALLS,
+        METRICS_SEED, PROGRAM_STATE_SEED, RISK_SCORE_THRESHOLD,
+    },
+    error::GuardError,
+    ring_buffer::RingBuffer,
+};
+
+/// Kind of state an account holds, told apart by the discriminator its
+/// header starts with
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountType {
+    ProgramState,
+    SecurityAnalysis,
+    Metrics,
+}
+
+/// Prefix of every account the program owns
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccountHeader {
+    /// Identifies the `AccountType`
+    pub discriminator: [u8; 8],
+    /// Layout of the fields following the header
+    pub version: u8,
+}
+
+/// State stored behind an `AccountHeader`
+///
+/// Layout v1 predates the header and holds the histories inline. v2 added
+/// the header, moved the histories into ring buffers and keyed metrics
+/// accounts by the program they measure.
+pub trait VersionedAccount: BorshSerialize + BorshDeserialize {
+    const ACCOUNT_TYPE: AccountType;
+
+    /// Reads an account of this type in the current layout
+    fn load(data: &[u8]) -> Result<Self, ProgramError> {
+        match AccountHeader::read(data, Self::ACCOUNT_TYPE) {
+            Some(header) if header.version == ACCOUNT_LAYOUT_VERSION => Ok(Self::deserialize(&mut &data[..])?),
+            Some(header) => {
+                msg!(
+                    "{:?} account has layout v{}, expected v{}",
+                    Self::ACCOUNT_TYPE,
+                    header.version,
+                    ACCOUNT_LAYOUT_VERSION
+                );
+                Err(GuardError::InvalidAccountData.into())
+            }
+            None => {
+                msg!("Not a {:?} account, or one that needs MigrateAccount", Self::ACCOUNT_TYPE);
+                Err(GuardError::InvalidAccountData.into())
+            }
+        }
+    }
+
+    /// Rewrites an account of this type from an older layout in the current
+    /// one; `data` must already be large enough for it
+    fn upgrade(data: &mut [u8]) -> Result<(), ProgramError>;
+}
+
+/// State followed by a ring buffer history taking the rest of the account
+pub trait HistoryAccount: VersionedAccount + MaxSize {
+    type Entry: BorshSerialize + BorshDeserialize + MaxSize;
+
+    /// Bytes taken by an account with room for `capacity` history entries
+    fn space(capacity: usize) -> usize {
+        Self::MAX_SIZE + RingBuffer::<Self::Entry>::space(capacity)
+    }
+
+    /// History stored after the fields of `data`
+    fn history(data: &mut [u8]) -> Result<RingBuffer<'_, Self::Entry>, ProgramError> {
+        RingBuffer::new(data.get_mut(Self::MAX_SIZE..).unwrap_or_default())
+    }
+
+    /// Lays out an empty history after the fields of `data`, as long as the
+    /// account allows
+    fn init_history(data: &mut [u8]) -> Result<RingBuffer<'_, Self::Entry>, ProgramError> {
+        let history = data.get_mut(Self::MAX_SIZE..).unwrap_or_default();
+        let capacity = RingBuffer::<Self::Entry>::capacity_for(history.len());
+        if capacity == 0 {
+            msg!("Account too small for a history");
+            return Err(GuardError::InsufficientBufferSize.into());
+        }
+        RingBuffer::init(history, capacity)
+    }
+}
 
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub struct ProgramState {
+    pub header: AccountHeader,
     /// The account that can update program settings
     pub authority: Pubkey,
     /// Program initialization timestamp
@@ -21,30 +114,127 @@ pub struct ProgramState {
     pub transactions_per_second: u64,
     /// Current average block time
     pub average_block_time: u64,
+    /// Canonical bump of the program state PDA
+    pub bump: u8,
+    /// Authority nominated by `ProposeAuthority`, awaiting acceptance
+    pub pending_authority: Option<Pubkey>,
+    /// Scoring applied to analysis findings
+    pub risk_model: RiskModel,
+}
+
+/// Outcome of an analysis on a 0-100 scale where higher is safer: a program
+/// without findings scores 100 and every finding lowers the score
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct SafetyScore(u8);
+
+/// Coarse risk bucket of a `SafetyScore`, riskier levels compare greater
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RiskLevel {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+/// Turns findings into a score: each finding deducts its detector weight
+/// scaled by its severity multiplier from `ceiling`, bottoming out at `floor`
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct RiskModel {
+    /// Base deduction per detector, indexed by `DetectorId`
+    pub detector_weights: [u8; MAX_DETECTORS],
+    /// Percentage of the detector weight applied, indexed by `Severity`
+    pub severity_multipliers: [u8; Severity::COUNT],
+    /// Lowest safety score an analysis can produce
+    pub floor: u8,
+    /// Safety score of a program without findings
+    pub ceiling: u8,
+    /// Minimum safety score for a passing verdict
+    pub pass_threshold: u8,
+    /// Compute units the target's entrypoint is expected to stay within
+    pub compute_budget: u32,
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub struct SecurityAnalysisState {
+    pub header: AccountHeader,
     /// Target program being analyzed
     pub target_program: Pubkey,
     /// Last analysis timestamp
     pub last_analysis: UnixTimestamp,
-    /// Risk score (0-100)
-    pub risk_score: u8,
+    /// Safety score of the latest analysis, higher is safer
+    pub safety_score: SafetyScore,
     /// Number of vulnerabilities found
     pub vulnerability_count: u16,
     /// Analysis status
     pub status: AnalysisStatus,
-    /// History of analysis results
-    pub analysis_history: Vec<AnalysisResult>,
     /// Detection patterns version
     pub patterns_version: u16,
+    /// Upgrade authority of the target, if it is an upgradeable program
+    pub upgrade_authority: Option<Pubkey>,
+    /// Slot the analyzed code was last deployed at (0 if unknown)
+    pub last_deploy_slot: u64,
+    /// Findings of the latest analysis, capped at `MAX_STORED_FINDINGS`
+    pub findings: Vec<Finding>,
+    /// Whether the latest safety score met the risk model's pass threshold
+    pub passed: bool,
+    /// Syscalls the analyzed code calls, capped at `MAX_STORED_SYSCALLS`
+    pub syscalls: Vec<SyscallCount>,
+    /// PDA derivations in the analyzed code, capped at `MAX_STORED_PDA_DERIVATIONS`
+    pub pda_derivations: Vec<PdaDerivation>,
+}
+
+/// A call site deriving a program address
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PdaDerivation {
+    /// Instruction index in `.text` of the syscall
+    pub offset: u32,
+    pub syscall: PdaSyscall,
+    pub seeds: SeedsPattern,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+pub struct Finding {
+    /// Detector that reported the issue
+    pub detector: DetectorId,
+    pub severity: Severity,
+    /// Detector-specific variant of the issue
+    pub code: u16,
+    /// Instruction index in `.text` the finding points at
+    pub offset: Option<u32>,
+    /// Entry instruction index of the enclosing function
+    pub function: Option<u32>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DetectorId {
+    UpgradeAuthority,
+    ProgramSize,
+    SuspiciousPattern,
+    ResourceUsage,
+    UnreachableCode,
+    NonTerminatingFunction,
+    MissingSignerCheck,
+    MissingOwnerCheck,
+    ArbitraryCpi,
+    UncheckedArithmetic,
+    TypeCosplay,
+    NonCanonicalBump,
+    AccountRevival,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Low,
+    Medium,
+    High,
+    Critical,
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
 pub struct AnalysisResult {
     pub timestamp: UnixTimestamp,
-    pub risk_score: u8,
+    pub safety_score: SafetyScore,
     pub vulnerability_count: u16,
     pub status: AnalysisStatus,
 }
@@ -59,6 +249,11 @@ pub enum AnalysisStatus {
 
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub struct MetricsState {
+    pub header: AccountHeader,
+    /// Program the metrics are recorded for
+    pub target_program: Pubkey,
+    /// Bump seed of the metrics PDA
+    pub bump: u8,
     /// Total transactions analyzed
     pub total_transactions: u64,
     /// Total gas used
@@ -69,35 +264,316 @@ pub struct MetricsState {
     pub success_rate: u8,
     /// Last metrics update
     pub last_update: UnixTimestamp,
-    /// Historical gas usage tracking
-    pub gas_history: Vec<GasMetric>,
     /// Peak gas usage
     pub peak_gas_used: u64,
     /// Transaction error count
     pub error_count: u64,
 }
 
-#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
 pub struct GasMetric {
     pub timestamp: UnixTimestamp,
     pub gas_used: u64,
     pub success: bool,
 }
 
+/// `ProgramState` of layout v1, at an address of the client's choosing
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct LegacyProgramState {
+    pub authority: Pubkey,
+    pub initialized_at: UnixTimestamp,
+    pub last_updated: UnixTimestamp,
+    pub transactions_per_second: u64,
+    pub average_block_time: u64,
+}
+
+/// `SecurityAnalysisState` of layout v1, holding its history inline
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct LegacySecurityAnalysisState {
+    pub target_program: Pubkey,
+    pub last_analysis: UnixTimestamp,
+    /// Higher is safer despite the name, like the `SafetyScore` it became
+    pub risk_score: u8,
+    pub vulnerability_count: u16,
+    pub status: AnalysisStatus,
+    /// Entries encode the same as the current `AnalysisResult`
+    pub analysis_history: Vec<AnalysisResult>,
+    pub patterns_version: u16,
+}
+
+/// `MetricsState` of layout v1, holding its history inline
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct LegacyMetricsState {
+    pub total_transactions: u64,
+    pub total_gas_used: u64,
+    pub avg_gas_used: u64,
+    pub success_rate: u8,
+    pub last_update: UnixTimestamp,
+    pub gas_history: Vec<GasMetric>,
+    pub peak_gas_used: u64,
+    pub error_count: u64,
+}
+
+impl AccountType {
+    pub fn discriminator(self) -> [u8; 8] {
+        match self {
+            Self::ProgramState => *b"sg:state",
+            Self::SecurityAnalysis => *b"sg:anlys",
+            Self::Metrics => *b"sg:metrc",
+        }
+    }
+}
+
+impl AccountHeader {
+    /// Header of a new account in the current layout
+    pub fn new(account_type: AccountType) -> Self {
+        Self {
+            discriminator: account_type.discriminator(),
+            version: ACCOUNT_LAYOUT_VERSION,
+        }
+    }
+
+    /// Header at the start of `data`, if it belongs to an `account_type` account
+    pub fn read(data: &[u8], account_type: AccountType) -> Option<Self> {
+        let header = Self::deserialize(&mut data.get(..Self::MAX_SIZE)?).ok()?;
+        (header.discriminator == account_type.discriminator()).then_some(header)
+    }
+
+    /// Type of the account `data` holds, judging by its discriminator
+    pub fn account_type(data: &[u8]) -> Option<AccountType> {
+        [AccountType::ProgramState, AccountType::SecurityAnalysis, AccountType::Metrics]
+            .into_iter()
+            .find(|&account_type| Self::read(data, account_type).is_some())
+    }
+
+    /// Fields of a v1 account, which has no header
+    pub fn legacy_body(data: &[u8], account_type: AccountType) -> Result<&[u8], ProgramError> {
+        // Only header-less v1 accounts may be read as any type
+        if let Some(other) = Self::account_type(data).filter(|&other| other != account_type) {
+            msg!("Account holds {:?}, not {:?}", other, account_type);
+            return Err(GuardError::InvalidAccountData.into());
+        }
+        match Self::read(data, account_type) {
+            None => Ok(data),
+            Some(header) => {
+                msg!("{:?} account has layout v{}, nothing to migrate", account_type, header.version);
+                Err(GuardError::InvalidAccountData.into())
+            }
+        }
+    }
+
+    /// Whether `data` has never been written
+    pub fn is_uninitialized(data: &[u8]) -> bool {
+        data.iter().take(Self::MAX_SIZE).all(|&byte| byte == 0)
+    }
+}
+
+/// Largest Borsh encoding of a type, with every collection at its cap
+pub trait MaxSize {
+    const MAX_SIZE: usize;
+}
+
+macro_rules! impl_max_size {
+    ($($ty:ty => $size:expr),* $(,)?) => {
+        $(impl MaxSize for $ty {
+            const MAX_SIZE: usize = $size;
+        })*
+    };
+}
+
+impl_max_size! {
+    bool => 1,
+    u8 => 1,
+    u16 => 2,
+    u32 => 4,
+    u64 => 8,
+    i64 => 8,
+    Pubkey => 32,
+    // Fieldless enums encode as their variant index
+    AnalysisStatus => 1,
+    DetectorId => 1,
+    Severity => 1,
+    PdaSyscall => 1,
+    SeedsPattern => 1,
+    SafetyScore => 1,
+}
+
+impl<T: MaxSize> MaxSize for Option<T> {
+    const MAX_SIZE: usize = 1 + T::MAX_SIZE;
+}
+
+impl<T: MaxSize, const N: usize> MaxSize for [T; N] {
+    const MAX_SIZE: usize = N * T::MAX_SIZE;
+}
+
+/// Largest encoding of a `Vec` of at most `cap` elements
+const fn vec_max_size<T: MaxSize>(cap: usize) -> usize {
+    4 + cap * T::MAX_SIZE
+}
+
+impl MaxSize for AccountHeader {
+    const MAX_SIZE: usize = <[u8; 8]>::MAX_SIZE + u8::MAX_SIZE;
+}
+
+impl MaxSize for ProgramState {
+    const MAX_SIZE: usize = AccountHeader::MAX_SIZE
+        + Pubkey::MAX_SIZE // authority
+        + 2 * UnixTimestamp::MAX_SIZE // initialized_at, last_updated
+        + 2 * u64::MAX_SIZE // network stats
+        + u8::MAX_SIZE // bump
+        + Option::<Pubkey>::MAX_SIZE // pending_authority
+        + RiskModel::MAX_SIZE;
+}
+
+impl MaxSize for RiskModel {
+    const MAX_SIZE: usize = <[u8; MAX_DETECTORS]>::MAX_SIZE
+        + <[u8; Severity::COUNT]>::MAX_SIZE
+        + <[u8; 3]>::MAX_SIZE // floor, ceiling, pass_threshold
+        + u32::MAX_SIZE; // compute_budget
+}
+
+impl MaxSize for SecurityAnalysisState {
+    const MAX_SIZE: usize = AccountHeader::MAX_SIZE
+        + Pubkey::MAX_SIZE // target_program
+        + UnixTimestamp::MAX_SIZE // last_analysis
+        + SafetyScore::MAX_SIZE
+        + u16::MAX_SIZE // vulnerability_count
+        + AnalysisStatus::MAX_SIZE
+        + u16::MAX_SIZE // patterns_version
+        + Option::<Pubkey>::MAX_SIZE // upgrade_authority
+        + u64::MAX_SIZE // last_deploy_slot
+        + vec_max_size::<Finding>(MAX_STORED_FINDINGS)
+        + bool::MAX_SIZE // passed
+        + vec_max_size::<SyscallCount>(MAX_STORED_SYSCALLS)
+        + vec_max_size::<PdaDerivation>(MAX_STORED_PDA_DERIVATIONS);
+}
+
+impl MaxSize for AnalysisResult {
+    const MAX_SIZE: usize =
+        UnixTimestamp::MAX_SIZE + SafetyScore::MAX_SIZE + u16::MAX_SIZE + AnalysisStatus::MAX_SIZE;
+}
+
+impl MaxSize for Finding {
+    const MAX_SIZE: usize = DetectorId::MAX_SIZE
+        + Severity::MAX_SIZE
+        + u16::MAX_SIZE // code
+        + 2 * Option::<u32>::MAX_SIZE; // offset, function
+}
+
+impl MaxSize for SyscallCount {
+    const MAX_SIZE: usize = u32::MAX_SIZE + u16::MAX_SIZE;
+}
+
+impl MaxSize for PdaDerivation {
+    const MAX_SIZE: usize = u32::MAX_SIZE + PdaSyscall::MAX_SIZE + SeedsPattern::MAX_SIZE;
+}
+
+impl MaxSize for MetricsState {
+    const MAX_SIZE: usize = AccountHeader::MAX_SIZE
+        + Pubkey::MAX_SIZE // target_program
+        + u8::MAX_SIZE // bump
+        + 3 * u64::MAX_SIZE // transaction and gas totals
+        + u8::MAX_SIZE // success_rate
+        + UnixTimestamp::MAX_SIZE // last_update
+        + 2 * u64::MAX_SIZE; // peak_gas_used, error_count
+}
+
+impl MaxSize for GasMetric {
+    const MAX_SIZE: usize = UnixTimestamp::MAX_SIZE + u64::MAX_SIZE + bool::MAX_SIZE;
+}
+
+impl VersionedAccount for ProgramState {
+    const ACCOUNT_TYPE: AccountType = AccountType::ProgramState;
+
+    fn upgrade(data: &mut [u8]) -> Result<(), ProgramError> {
+        // v1 accounts were created by clients, so none lives at the program
+        // state PDA that `Initialize` creates in their place
+        LegacyProgramState::deserialize(&mut AccountHeader::legacy_body(data, Self::ACCOUNT_TYPE)?)?;
+        msg!("Program state before v2 is replaced by the program state PDA with Initialize");
+        Err(GuardError::InvalidAccountData.into())
+    }
+}
+
+impl VersionedAccount for SecurityAnalysisState {
+    const ACCOUNT_TYPE: AccountType = AccountType::SecurityAnalysis;
+
+    fn upgrade(data: &mut [u8]) -> Result<(), ProgramError> {
+        let legacy = LegacySecurityAnalysisState::deserialize(&mut AccountHeader::legacy_body(
+            data,
+            Self::ACCOUNT_TYPE,
+        )?)?;
+        let state = Self {
+            header: AccountHeader::new(Self::ACCOUNT_TYPE),
+            target_program: legacy.target_program,
+            last_analysis: legacy.last_analysis,
+            safety_score: SafetyScore::new(legacy.risk_score),
+            vulnerability_count: legacy.vulnerability_count,
+            status: legacy.status,
+            patterns_version: legacy.patterns_version,
+            upgrade_authority: None,
+            last_deploy_slot: 0,
+            findings: Vec::new(),
+            passed: false,
+            syscalls: Vec::new(),
+            pda_derivations: Vec::new(),
+        };
+        state.serialize(&mut &mut data[..])?;
+
+        let mut history = Self::init_history(data)?;
+        for result in &legacy.analysis_history {
+            history.push(result)?;
+        }
+        Ok(())
+    }
+}
+
+impl HistoryAccount for SecurityAnalysisState {
+    type Entry = AnalysisResult;
+}
+
+impl HistoryAccount for MetricsState {
+    type Entry = GasMetric;
+}
+
+impl VersionedAccount for MetricsState {
+    const ACCOUNT_TYPE: AccountType = AccountType::Metrics;
+
+    fn upgrade(data: &mut [u8]) -> Result<(), ProgramError> {
+        // v1 accounts live at arbitrary addresses and do not record the
+        // program they measure, so they move to its PDA instead
+        AccountHeader::legacy_body(data, Self::ACCOUNT_TYPE)?;
+        msg!("Metrics accounts before v2 move to the target's metrics PDA with MigrateMetrics");
+        Err(GuardError::InvalidAccountData.into())
+    }
+}
+
 impl ProgramState {
-    pub const SPACE: usize = 32 + 8 + 8 + 8 + 8; // pubkey + timestamps + network stats
+    pub const SPACE: usize = Self::MAX_SIZE;
 
-    pub fn new(authority: Pubkey, current_timestamp: UnixTimestamp) -> Self {
+    pub fn new(authority: Pubkey, bump: u8, current_timestamp: UnixTimestamp) -> Self {
         Self {
+            header: AccountHeader::new(Self::ACCOUNT_TYPE),
             authority,
             initialized_at: current_timestamp,
             last_updated: current_timestamp,
             transactions_per_second: 0,
             average_block_time: 0,
+            bump,
+            pending_authority: None,
+            risk_model: RiskModel::default(),
         }
     }
 
+    /// Derives the canonical program state address and its bump
+    pub fn find_address(program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[PROGRAM_STATE_SEED], program_id)
+    }
+
     pub fn check_authority(&self, authority_pubkey: &Pubkey) -> Result<(), ProgramError> {
+        if self.is_renounced() {
+            msg!("Authority has been renounced");
+            return Err(GuardError::UnauthorizedAccount.into());
+        }
         if &self.authority != authority_pubkey {
             msg!("Invalid authority");
             return Err(GuardError::UnauthorizedAccount.into());
@@ -105,6 +581,34 @@ impl ProgramState {
         Ok(())
     }
 
+    /// Returns true once the authority has been renounced for good
+    pub fn is_renounced(&self) -> bool {
+        self.authority == Pubkey::default()
+    }
+
+    pub fn propose_authority(&mut self, new_authority: Pubkey) {
+        self.pending_authority = Some(new_authority);
+    }
+
+    pub fn accept_authority(&mut self, signer_pubkey: &Pubkey) -> Result<(), ProgramError> {
+        match self.pending_authority {
+            Some(pending) if &pending == signer_pubkey => {
+                self.authority = pending;
+                self.pending_authority = None;
+                Ok(())
+            }
+            _ => {
+                msg!("Signer is not the pending authority");
+                Err(GuardError::UnauthorizedAccount.into())
+            }
+        }
+    }
+
+    pub fn renounce_authority(&mut self) {
+        self.authority = Pubkey::default();
+        self.pending_authority = None;
+    }
+
     pub fn update_network_stats(
         &mut self,
         tps: u64,
@@ -119,81 +623,353 @@ impl ProgramState {
 }
 
 impl SecurityAnalysisState {
-    pub const SPACE: usize = 32 + 8 + 1 + 2 + 1 + 256 + 2; // Base fields + history + version
+    pub const SPACE: usize =
+        Self::MAX_SIZE + RingBuffer::<AnalysisResult>::space(DEFAULT_ANALYSIS_HISTORY); // fields + history
 
     pub fn new(target_program: Pubkey, current_timestamp: UnixTimestamp) -> Self {
         Self {
+            header: AccountHeader::new(Self::ACCOUNT_TYPE),
             target_program,
             last_analysis: current_timestamp,
-            risk_score: 0,
+            safety_score: SafetyScore::default(),
             vulnerability_count: 0,
             status: AnalysisStatus::Pending,
-            analysis_history: Vec::with_capacity(10),
             patterns_version: 1,
+            upgrade_authority: None,
+            last_deploy_slot: 0,
+            findings: Vec::new(),
+            passed: false,
+            syscalls: Vec::new(),
+            pda_derivations: Vec::new(),
         }
     }
 
+    /// Records who can upgrade the analyzed code and when it was deployed
+    pub fn record_deployment(&mut self, last_deploy_slot: u64, upgrade_authority: Option<Pubkey>) {
+        self.last_deploy_slot = last_deploy_slot;
+        self.upgrade_authority = upgrade_authority;
+    }
+
+    pub fn set_verdict(&mut self, passed: bool) {
+        self.passed = passed;
+    }
+
     pub fn update_analysis(
         &mut self,
-        risk_score: u8,
+        history: &mut RingBuffer<AnalysisResult>,
+        safety_score: SafetyScore,
         vulnerability_count: u16,
         current_timestamp: UnixTimestamp,
-    ) {
+    ) -> Result<(), ProgramError> {
         // Create new analysis result
         let result = AnalysisResult {
             timestamp: current_timestamp,
-            risk_score,
+            safety_score,
             vulnerability_count,
             status: AnalysisStatus::Completed,
         };
 
         // Update current state
-        self.risk_score = risk_score;
+        self.safety_score = safety_score;
         self.vulnerability_count = vulnerability_count;
         self.last_analysis = current_timestamp;
         self.status = AnalysisStatus::Completed;
 
-        // Add to history, maintaining fixed size
-        if self.analysis_history.len() >= 10 {
-            self.analysis_history.remove(0);
+        // Add to history, evicting the oldest result once full
+        history.push(&result)
+    }
+
+    /// Replaces the stored findings with those of the latest analysis, most
+    /// severe first so the cap drops the least severe ones
+    pub fn set_findings(&mut self, mut findings: Vec<Finding>) {
+        findings.sort_by_key(|finding| Reverse(finding.severity));
+        if findings.len() > MAX_STORED_FINDINGS {
+            msg!("Storing {} of {} findings", MAX_STORED_FINDINGS, findings.len());
+            findings.truncate(MAX_STORED_FINDINGS);
         }
-        self.analysis_history.push(result);
+        self.findings = findings;
     }
 
-    pub fn get_risk_trend(&self) -> Option<i8> {
-        if self.analysis_history.len() < 2 {
-            return None;
+    /// Replaces the syscall inventory with that of the latest analysis
+    pub fn set_syscalls(&mut self, mut syscalls: Vec<SyscallCount>) {
+        if syscalls.len() > MAX_STORED_SYSCALLS {
+            msg!("Storing {} of {} syscalls", MAX_STORED_SYSCALLS, syscalls.len());
+            syscalls.truncate(MAX_STORED_SYSCALLS);
         }
+        self.syscalls = syscalls;
+    }
 
-        let latest = self.analysis_history.last()?;
-        let previous = self.analysis_history.get(self.analysis_history.len() - 2)?;
-        
-        Some(latest.risk_score as i8 - previous.risk_score as i8)
+    /// Replaces the PDA derivations with those of the latest analysis
+    pub fn set_pda_derivations(&mut self, mut pda_derivations: Vec<PdaDerivation>) {
+        if pda_derivations.len() > MAX_STORED_PDA_DERIVATIONS {
+            msg!(
+                "Storing {} of {} PDA derivations",
+                MAX_STORED_PDA_DERIVATIONS,
+                pda_derivations.len()
+            );
+            pda_derivations.truncate(MAX_STORED_PDA_DERIVATIONS);
+        }
+        self.pda_derivations = pda_derivations;
+    }
+
+    /// What the analyzed code can do, judging by the syscalls it calls
+    pub fn capabilities(&self) -> Capabilities {
+        let mut capabilities = Capabilities::default();
+        for syscall in &self.syscalls {
+            if let Some(name) = syscall.name() {
+                capabilities.insert(Capabilities::of(name));
+            }
+        }
+        capabilities
+    }
+
+    pub fn risk_level(&self) -> RiskLevel {
+        self.safety_score.risk_level()
+    }
+}
+
+impl RingBuffer<'_, AnalysisResult> {
+    /// Change in risk between the last two analyses, positive when the
+    /// program got riskier (its safety score dropped)
+    pub fn risk_trend(&self) -> Option<i8> {
+        let latest = self.last()?;
+        let previous = self.get(self.len().checked_sub(2)?)?;
+
+        Some(previous.safety_score.value() as i8 - latest.safety_score.value() as i8)
+    }
+}
+
+impl SafetyScore {
+    pub const MIN: Self = Self(0);
+    pub const MAX: Self = Self(100);
+
+    /// Scores above 100 are clamped
+    pub fn new(value: u8) -> Self {
+        Self(value.min(Self::MAX.0))
+    }
+
+    pub fn value(self) -> u8 {
+        self.0
+    }
+
+    pub fn risk_level(self) -> RiskLevel {
+        match self.0 {
+            80.. => RiskLevel::Low,
+            60..=79 => RiskLevel::Medium,
+            40..=59 => RiskLevel::High,
+            _ => RiskLevel::Critical,
+        }
+    }
+}
+
+impl std::fmt::Display for SafetyScore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/100", self.0)
+    }
+}
+
+impl Default for RiskModel {
+    fn default() -> Self {
+        let mut detector_weights = [0; MAX_DETECTORS];
+        detector_weights[DetectorId::UpgradeAuthority as usize] = 20;
+        detector_weights[DetectorId::ProgramSize as usize] = 20;
+        detector_weights[DetectorId::SuspiciousPattern as usize] = 30;
+        detector_weights[DetectorId::ResourceUsage as usize] = 20;
+        detector_weights[DetectorId::UnreachableCode as usize] = 10;
+        detector_weights[DetectorId::NonTerminatingFunction as usize] = 30;
+        detector_weights[DetectorId::MissingSignerCheck as usize] = 40;
+        detector_weights[DetectorId::MissingOwnerCheck as usize] = 30;
+        detector_weights[DetectorId::ArbitraryCpi as usize] = 40;
+        detector_weights[DetectorId::UncheckedArithmetic as usize] = 20;
+        detector_weights[DetectorId::TypeCosplay as usize] = 30;
+        detector_weights[DetectorId::NonCanonicalBump as usize] = 20;
+        detector_weights[DetectorId::AccountRevival as usize] = 30;
+
+        Self {
+            detector_weights,
+            // Info, Low, Medium, High, Critical
+            severity_multipliers: [0, 25, 50, 100, 150],
+            floor: 0,
+            ceiling: 100,
+            pass_threshold: RISK_SCORE_THRESHOLD,
+            compute_budget: DEFAULT_COMPUTE_BUDGET,
+        }
+    }
+}
+
+impl RiskModel {
+    pub fn validate(&self) -> Result<(), ProgramError> {
+        if self.floor > self.ceiling
+            || self.ceiling > 100
+            || self.pass_threshold < self.floor
+            || self.pass_threshold > self.ceiling
+        {
+            msg!("Risk model bounds must satisfy floor <= threshold <= ceiling <= 100");
+            return Err(GuardError::InvalidInstructionData.into());
+        }
+        if self.compute_budget == 0 || self.compute_budget > MAX_COMPUTE_UNIT_LIMIT {
+            msg!("Compute budget must be between 1 and {}", MAX_COMPUTE_UNIT_LIMIT);
+            return Err(GuardError::InvalidInstructionData.into());
+        }
+        Ok(())
+    }
+
+    /// Deduction of a single finding
+    pub fn deduction(&self, finding: &Finding) -> u32 {
+        let weight = self.detector_weights[finding.detector as usize] as u32;
+        let multiplier = self.severity_multipliers[finding.severity as usize] as u32;
+        weight * multiplier / 100
+    }
+
+    pub fn score(&self, findings: &[Finding]) -> SafetyScore {
+        let total: u32 = findings.iter().map(|finding| self.deduction(finding)).sum();
+        let score = (self.ceiling as u32)
+            .saturating_sub(total)
+            .max(self.floor as u32);
+        SafetyScore::new(score as u8)
+    }
+
+    pub fn passes(&self, score: SafetyScore) -> bool {
+        score.value() >= self.pass_threshold
+    }
+}
+
+impl DetectorId {
+    pub const COUNT: usize = Self::AccountRevival as usize + 1; // AccountRevival is the last detector
+}
+
+// Every detector needs a slot in `RiskModel::detector_weights`
+const _: () = assert!(DetectorId::COUNT <= MAX_DETECTORS);
+
+impl Severity {
+    pub const COUNT: usize = 5;
+}
+
+impl Finding {
+    pub fn new(detector: DetectorId, severity: Severity) -> Self {
+        Self {
+            detector,
+            severity,
+            code: 0,
+            offset: None,
+            function: None,
+        }
+    }
+
+    pub fn with_code(mut self, code: u16) -> Self {
+        self.code = code;
+        self
+    }
+
+    pub fn at(mut self, offset: usize) -> Self {
+        self.offset = Some(offset as u32);
+        self
+    }
+
+    pub fn in_function(mut self, entry: usize) -> Self {
+        self.function = Some(entry as u32);
+        self
+    }
+
+    /// How the target program's authors can address the finding
+    pub fn recommendation(&self) -> &'static str {
+        match (self.detector, self.code) {
+            (DetectorId::UpgradeAuthority, 1) => {
+                "Review the members and threshold of the upgrade multisig"
+            }
+            (DetectorId::UpgradeAuthority, _) => {
+                "Move the upgrade authority to a multisig or make the program immutable"
+            }
+            (DetectorId::ProgramSize, _) => "Remove unused code or split the program",
+            (DetectorId::SuspiciousPattern, _) => "Remove the unconditional self-loop",
+            (DetectorId::ResourceUsage, 1) => "Bound the loops and recursion reachable from the entrypoint",
+            (DetectorId::ResourceUsage, 2) => {
+                "Split the work across instructions, it cannot fit in a transaction's compute limit"
+            }
+            (DetectorId::ResourceUsage, _) => "Reduce the work done on the most expensive path",
+            (DetectorId::UnreachableCode, _) => "Remove the dead code or fix the branches guarding it",
+            (DetectorId::NonTerminatingFunction, _) => "Give the function a path that returns",
+            (DetectorId::MissingSignerCheck, _) => {
+                "Require the authority to sign before writing account data or moving lamports"
+            }
+            (DetectorId::MissingOwnerCheck, _) => {
+                "Compare the account owner with the program id before reading account data"
+            }
+            (DetectorId::ArbitraryCpi, 1) => "Invoke a fixed program id so it can be verified",
+            (DetectorId::ArbitraryCpi, _) => {
+                "Compare the invoked program id with the expected program before invoking"
+            }
+            (DetectorId::UncheckedArithmetic, 1) => "Enable overflow-checks in the release profile",
+            (DetectorId::UncheckedArithmetic, _) => {
+                "Use checked arithmetic on amounts read from accounts before writing them back"
+            }
+            (DetectorId::TypeCosplay, _) => {
+                "Compare the account discriminator with the expected type before reading its fields"
+            }
+            (DetectorId::NonCanonicalBump, _) => {
+                "Derive the bump with find_program_address, or check a supplied bump against it"
+            }
+            (DetectorId::AccountRevival, _) => {
+                "Zero the account data or write a closed-account discriminator when closing it"
+            }
+        }
     }
 }
 
 impl MetricsState {
-    pub const SPACE: usize = 8 + 8 + 8 + 1 + 8 + 256 + 8 + 8; // Base fields + history + additional metrics
+    pub const SPACE: usize = Self::MAX_SIZE + RingBuffer::<GasMetric>::space(DEFAULT_GAS_HISTORY); // fields + history
 
-    pub fn new(current_timestamp: UnixTimestamp) -> Self {
+    pub fn new(target_program: Pubkey, bump: u8, current_timestamp: UnixTimestamp) -> Self {
         Self {
+            header: AccountHeader::new(Self::ACCOUNT_TYPE),
+            target_program,
+            bump,
             total_transactions: 0,
             total_gas_used: 0,
             avg_gas_used: 0,
             success_rate: 0,
             last_update: current_timestamp,
-            gas_history: Vec::with_capacity(100),
             peak_gas_used: 0,
             error_count: 0,
         }
     }
 
+    /// Derives the metrics address of `target_program` and its bump
+    pub fn find_address(target_program: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[METRICS_SEED, target_program.as_ref()], program_id)
+    }
+
+    /// Reads a v1 metrics account as the metrics of `target_program`, along
+    /// with its history from oldest to newest
+    pub fn from_legacy(
+        data: &[u8],
+        target_program: Pubkey,
+        bump: u8,
+    ) -> Result<(Self, Vec<GasMetric>), ProgramError> {
+        let legacy = LegacyMetricsState::deserialize(&mut AccountHeader::legacy_body(data, Self::ACCOUNT_TYPE)?)?;
+
+        let state = Self {
+            header: AccountHeader::new(Self::ACCOUNT_TYPE),
+            target_program,
+            bump,
+            total_transactions: legacy.total_transactions,
+            total_gas_used: legacy.total_gas_used,
+            avg_gas_used: legacy.avg_gas_used,
+            success_rate: legacy.success_rate,
+            last_update: legacy.last_update,
+            peak_gas_used: legacy.peak_gas_used,
+            error_count: legacy.error_count,
+        };
+        Ok((state, legacy.gas_history))
+    }
+
     pub fn record_transaction(
         &mut self,
+        history: &mut RingBuffer<GasMetric>,
         gas_used: u64,
         success: bool,
         current_timestamp: UnixTimestamp,
-    ) {
+    ) -> Result<(), ProgramError> {
         // Update basic metrics
         self.total_transactions = self.total_transactions.saturating_add(1);
         self.total_gas_used = self.total_gas_used.saturating_add(gas_used);
@@ -221,22 +997,17 @@ impl MetricsState {
             success,
         };
 
-        if self.gas_history.len() >= 100 {
-            self.gas_history.remove(0);
-        }
-        self.gas_history.push(metric);
-
         self.last_update = current_timestamp;
+        history.push(&metric)
     }
+}
 
-    pub fn get_gas_trend(&self) -> Option<i64> {
-        if self.gas_history.len() < 2 {
-            return None;
-        }
+impl RingBuffer<'_, GasMetric> {
+    /// Change in gas used between the last two samples
+    pub fn gas_trend(&self) -> Option<i64> {
+        let latest = self.last()?;
+        let previous = self.get(self.len().checked_sub(2)?)?;
 
-        let latest = self.gas_history.last()?;
-        let previous = self.gas_history.get(self.gas_history.len() - 2)?;
-        
         Some(latest.gas_used as i64 - previous.gas_used as i64)
     }
 }
@@ -249,7 +1020,7 @@ mod tests {
     fn test_program_state() {
         let authority = Pubkey::new_unique();
         let timestamp = 1234567890;
-        let mut state = ProgramState::new(authority, timestamp);
+        let mut state = ProgramState::new(authority, 255, timestamp);
 
         assert_eq!(state.authority, authority);
         assert_eq!(state.initialized_at, timestamp);
@@ -260,43 +1031,317 @@ mod tests {
         assert_eq!(state.average_block_time, 500);
     }
 
+    #[test]
+    fn test_authority_handover() {
+        let authority = Pubkey::new_unique();
+        let new_authority = Pubkey::new_unique();
+        let mut state = ProgramState::new(authority, 255, 0);
+
+        // Nothing to accept before a proposal
+        assert!(state.accept_authority(&new_authority).is_err());
+
+        state.propose_authority(new_authority);
+        assert_eq!(state.authority, authority);
+        assert_eq!(borsh::to_vec(&state).unwrap().len(), ProgramState::SPACE);
+        assert!(state.accept_authority(&Pubkey::new_unique()).is_err());
+        assert!(state.accept_authority(&new_authority).is_ok());
+        assert_eq!(state.authority, new_authority);
+        assert_eq!(state.pending_authority, None);
+        assert!(state.check_authority(&new_authority).is_ok());
+        assert!(state.check_authority(&authority).is_err());
+
+        state.propose_authority(authority);
+        state.renounce_authority();
+        assert!(state.is_renounced());
+        assert_eq!(state.pending_authority, None);
+        assert!(state.check_authority(&new_authority).is_err());
+        assert!(state.check_authority(&Pubkey::default()).is_err());
+    }
+
     #[test]
     fn test_security_analysis_state() {
         let program = Pubkey::new_unique();
         let timestamp = 1234567890;
         let mut state = SecurityAnalysisState::new(program, timestamp);
+        let mut data = vec![0; SecurityAnalysisState::SPACE];
+        let mut history = SecurityAnalysisState::init_history(&mut data).unwrap();
 
         // Test initial state
         assert_eq!(state.status, AnalysisStatus::Pending);
         
         // Test multiple analysis updates
-        state.update_analysis(85, 3, timestamp + 100);
-        state.update_analysis(80, 4, timestamp + 200);
+        state.update_analysis(&mut history, SafetyScore::new(85), 3, timestamp + 100).unwrap();
+        state.update_analysis(&mut history, SafetyScore::new(80), 4, timestamp + 200).unwrap();
         
-        assert_eq!(state.risk_score, 80);
+        assert_eq!(state.safety_score, SafetyScore::new(80));
         assert_eq!(state.vulnerability_count, 4);
-        assert_eq!(state.analysis_history.len(), 2);
+        assert_eq!(history.len(), 2);
         
-        // Test risk trend
-        assert_eq!(state.get_risk_trend(), Some(-5));
+        // Test risk trend: a lower safety score means more risk
+        assert_eq!(history.risk_trend(), Some(5));
+        assert_eq!(state.risk_level(), RiskLevel::Low);
+    }
+
+    #[test]
+    fn test_safety_score() {
+        assert_eq!(SafetyScore::new(250), SafetyScore::MAX);
+        assert_eq!(borsh::to_vec(&SafetyScore::new(42)).unwrap(), vec![42]);
+
+        assert_eq!(SafetyScore::MAX.risk_level(), RiskLevel::Low);
+        assert_eq!(SafetyScore::new(80).risk_level(), RiskLevel::Low);
+        assert_eq!(SafetyScore::new(79).risk_level(), RiskLevel::Medium);
+        assert_eq!(SafetyScore::new(59).risk_level(), RiskLevel::High);
+        assert_eq!(SafetyScore::new(39).risk_level(), RiskLevel::Critical);
+        assert!(RiskLevel::Critical > RiskLevel::Low);
+    }
+
+    #[test]
+    fn test_risk_model() {
+        let model = RiskModel::default();
+        assert!(model.validate().is_ok());
+        assert_eq!(borsh::to_vec(&model).unwrap().len(), RiskModel::MAX_SIZE);
+
+        let single_key = Finding::new(DetectorId::UpgradeAuthority, Severity::High);
+        let multisig = Finding::new(DetectorId::UpgradeAuthority, Severity::Low);
+        assert_eq!(model.score(&[]), SafetyScore::MAX);
+        assert_eq!(model.score(std::slice::from_ref(&single_key)), SafetyScore::new(80));
+        assert_eq!(model.score(&[multisig]), SafetyScore::new(95));
+        assert!(model.passes(SafetyScore::new(80)));
+        assert!(!model.passes(SafetyScore::new(79)));
+
+        // Deductions stop at the floor
+        let strict = RiskModel {
+            floor: 30,
+            ..RiskModel::default()
+        };
+        assert_eq!(strict.score(&vec![single_key; 10]), SafetyScore::new(30));
+
+        let inverted = RiskModel {
+            floor: 90,
+            ceiling: 50,
+            ..RiskModel::default()
+        };
+        assert!(inverted.validate().is_err());
+
+        let unlimited = RiskModel {
+            compute_budget: MAX_COMPUTE_UNIT_LIMIT + 1,
+            ..RiskModel::default()
+        };
+        assert!(unlimited.validate().is_err());
+    }
+
+    #[test]
+    fn test_findings() {
+        let mut state = SecurityAnalysisState::new(Pubkey::new_unique(), 0);
+        let finding = Finding::new(DetectorId::SuspiciousPattern, Severity::High)
+            .with_code(1)
+            .at(12)
+            .in_function(4);
+        assert_eq!(borsh::to_vec(&finding).unwrap().len(), Finding::MAX_SIZE);
+
+        state.set_findings(vec![finding.clone(); MAX_STORED_FINDINGS + 3]);
+        assert_eq!(state.findings.len(), MAX_STORED_FINDINGS);
+
+        // The cap drops the least severe findings, wherever they were reported
+        let critical = Finding::new(DetectorId::ArbitraryCpi, Severity::Critical).at(40);
+        let mut findings = vec![Finding::new(DetectorId::UnreachableCode, Severity::Low); MAX_STORED_FINDINGS + 1];
+        findings.push(critical.clone());
+        state.set_findings(findings);
+        assert_eq!(state.findings.len(), MAX_STORED_FINDINGS);
+        assert_eq!(state.findings[0], critical);
+
+        state.set_findings(vec![finding.clone(); MAX_STORED_FINDINGS + 3]);
+
+        let derivation = PdaDerivation {
+            offset: 3,
+            syscall: PdaSyscall::Create,
+            seeds: SeedsPattern::Dynamic,
+        };
+        assert_eq!(borsh::to_vec(&derivation).unwrap().len(), PdaDerivation::MAX_SIZE);
+        state.set_pda_derivations(vec![derivation; MAX_STORED_PDA_DERIVATIONS + 1]);
+        assert_eq!(state.pda_derivations.len(), MAX_STORED_PDA_DERIVATIONS);
+
+        let encoded = borsh::to_vec(&state).unwrap();
+        let decoded = SecurityAnalysisState::try_from_slice(&encoded).unwrap();
+        assert_eq!(decoded.findings[0], finding);
+        assert_eq!(decoded.findings[0].offset, Some(12));
+        assert_eq!(decoded.findings[0].function, Some(4));
+
+        let owner_finding = Finding::new(DetectorId::MissingOwnerCheck, Severity::High);
+        assert!(owner_finding.recommendation().contains("owner"));
+        let cpi_finding = Finding::new(DetectorId::ArbitraryCpi, Severity::Critical);
+        assert!(cpi_finding.recommendation().contains("program id"));
+    }
+
+    #[test]
+    fn test_account_versioning() {
+        let result = AnalysisResult {
+            timestamp: 100,
+            safety_score: SafetyScore::new(70),
+            vulnerability_count: 1,
+            status: AnalysisStatus::Completed,
+        };
+        let legacy_analysis = LegacySecurityAnalysisState {
+            target_program: Pubkey::new_unique(),
+            last_analysis: 100,
+            risk_score: 70,
+            vulnerability_count: 1,
+            status: AnalysisStatus::Completed,
+            analysis_history: vec![result.clone()],
+            patterns_version: 1,
+        };
+
+        // v1 accounts hold the legacy fields alone
+        let v1 = borsh::to_vec(&legacy_analysis).unwrap();
+        let mut data = vec![0; SecurityAnalysisState::SPACE];
+        data[..v1.len()].copy_from_slice(&v1);
+        assert!(SecurityAnalysisState::load(&data).is_err());
+        SecurityAnalysisState::upgrade(&mut data).unwrap();
+        let upgraded = SecurityAnalysisState::load(&data).unwrap();
+        assert_eq!(upgraded.header, AccountHeader::new(AccountType::SecurityAnalysis));
+        assert_eq!(upgraded.target_program, legacy_analysis.target_program);
+        assert_eq!(upgraded.safety_score, SafetyScore::new(70));
+        assert!(upgraded.findings.is_empty());
+        let history = SecurityAnalysisState::history(&mut data).unwrap();
+        assert_eq!(history.iter().collect::<Vec<_>>(), vec![result]);
+        // Nothing is left to migrate
+        assert!(SecurityAnalysisState::upgrade(&mut data).is_err());
+
+        // A header of another type is never read as a header-less body
+        assert!(AccountHeader::legacy_body(&data, AccountType::Metrics).is_err());
+        assert!(MetricsState::upgrade(&mut data).is_err());
+
+        // v1 metrics accounts do not know their target program
+        let gas_history = vec![
+            GasMetric { timestamp: 100, gas_used: 1000, success: true },
+            GasMetric { timestamp: 200, gas_used: 2000, success: false },
+        ];
+        let legacy_metrics = LegacyMetricsState {
+            total_transactions: 2,
+            total_gas_used: 3000,
+            avg_gas_used: 1500,
+            success_rate: 50,
+            last_update: 200,
+            gas_history: gas_history.clone(),
+            peak_gas_used: 2000,
+            error_count: 1,
+        };
+        let mut data = borsh::to_vec(&legacy_metrics).unwrap();
+        data.resize(MetricsState::SPACE, 0);
+        assert!(MetricsState::upgrade(&mut data).is_err());
+
+        // but are read as the metrics of the program they are moved to
+        let target_program = Pubkey::new_unique();
+        let (state, history) = MetricsState::from_legacy(&data, target_program, 254).unwrap();
+        assert_eq!(state.target_program, target_program);
+        assert_eq!(state.bump, 254);
+        assert_eq!(state.total_gas_used, 3000);
+        assert_eq!(state.peak_gas_used, 2000);
+        assert_eq!(state.error_count, 1);
+        assert_eq!(history, gas_history);
+        let mut data = vec![0; MetricsState::SPACE];
+        MetricsState::new(target_program, 254, 0).serialize(&mut &mut data[..]).unwrap();
+        assert!(MetricsState::from_legacy(&data, target_program, 254).is_err());
+
+        // v1 program state is replaced rather than migrated
+        let mut data = borsh::to_vec(&LegacyProgramState {
+            authority: Pubkey::new_unique(),
+            initialized_at: 0,
+            last_updated: 0,
+            transactions_per_second: 0,
+            average_block_time: 0,
+        })
+        .unwrap();
+        data.resize(ProgramState::SPACE, 0);
+        assert!(ProgramState::upgrade(&mut data).is_err());
+
+        let program_state = ProgramState::new(Pubkey::new_unique(), 254, 0);
+        let current = borsh::to_vec(&program_state).unwrap();
+        // Accounts of another type or layout are rejected
+        assert!(ProgramState::load(&current).is_ok());
+        assert!(MetricsState::load(&current).is_err());
+        let mut future = current.clone();
+        future[8] = ACCOUNT_LAYOUT_VERSION + 1;
+        assert!(ProgramState::load(&future).is_err());
+        assert!(ProgramState::upgrade(&mut future).is_err());
+
+        assert!(AccountHeader::is_uninitialized(&[]));
+        assert!(AccountHeader::is_uninitialized(&[0; MetricsState::SPACE]));
+        assert!(!AccountHeader::is_uninitialized(&current));
+    }
+
+    #[test]
+    fn test_max_size() {
+        let mut program_state = ProgramState::new(Pubkey::new_unique(), 255, 0);
+        program_state.propose_authority(Pubkey::new_unique());
+        assert_eq!(borsh::to_vec(&program_state).unwrap().len(), ProgramState::SPACE);
+
+        let finding = Finding::new(DetectorId::ArbitraryCpi, Severity::Critical)
+            .at(1)
+            .in_function(0);
+        let derivation = PdaDerivation {
+            offset: 0,
+            syscall: PdaSyscall::TryFind,
+            seeds: SeedsPattern::Constant,
+        };
+        let mut analysis = SecurityAnalysisState::new(Pubkey::new_unique(), 0);
+        let mut data = vec![0; SecurityAnalysisState::SPACE];
+        let short = SecurityAnalysisState::init_history(&mut data[..SecurityAnalysisState::SPACE - 1]).unwrap();
+        assert_eq!(short.capacity(), DEFAULT_ANALYSIS_HISTORY - 1);
+        let mut history = SecurityAnalysisState::init_history(&mut data).unwrap();
+        for timestamp in 0..DEFAULT_ANALYSIS_HISTORY as i64 + 1 {
+            analysis.update_analysis(&mut history, SafetyScore::new(50), 1, timestamp).unwrap();
+        }
+        analysis.record_deployment(1, Some(Pubkey::new_unique()));
+        analysis.set_findings(vec![finding; MAX_STORED_FINDINGS]);
+        analysis.set_syscalls(vec![SyscallCount { hash: 0, call_sites: 1 }; MAX_STORED_SYSCALLS]);
+        analysis.set_pda_derivations(vec![derivation; MAX_STORED_PDA_DERIVATIONS]);
+        assert_eq!(borsh::to_vec(&analysis).unwrap().len(), SecurityAnalysisState::MAX_SIZE);
+        // The fields fill the account up to a full history
+        analysis.serialize(&mut &mut data[..]).unwrap();
+        let history = SecurityAnalysisState::history(&mut data).unwrap();
+        assert_eq!(history.len(), DEFAULT_ANALYSIS_HISTORY);
+        assert_eq!(history.get(0).unwrap().timestamp, 1);
+
+        let mut metrics = MetricsState::new(Pubkey::new_unique(), 255, 0);
+        let mut data = vec![0; MetricsState::SPACE];
+        let short = MetricsState::init_history(&mut data[..MetricsState::SPACE - 1]).unwrap();
+        assert_eq!(short.capacity(), DEFAULT_GAS_HISTORY - 1);
+        let mut history = MetricsState::init_history(&mut data).unwrap();
+        for timestamp in 0..DEFAULT_GAS_HISTORY as i64 + 1 {
+            metrics.record_transaction(&mut history, 1000, true, timestamp).unwrap();
+        }
+        assert_eq!(borsh::to_vec(&metrics).unwrap().len(), MetricsState::MAX_SIZE);
+        metrics.serialize(&mut &mut data[..]).unwrap();
+        let history = MetricsState::history(&mut data).unwrap();
+        assert_eq!(history.len(), DEFAULT_GAS_HISTORY);
+        assert_eq!(history.last().unwrap().timestamp, DEFAULT_GAS_HISTORY as i64);
     }
 
     #[test]
     fn test_metrics_state() {
         let timestamp = 1234567890;
-        let mut state = MetricsState::new(timestamp);
+        let mut state = MetricsState::new(Pubkey::new_unique(), 255, timestamp);
+        let mut data = vec![0; MetricsState::SPACE];
+        let mut history = MetricsState::init_history(&mut data).unwrap();
 
         // Test transaction recording
-        state.record_transaction(1000, true, timestamp + 100);
-        state.record_transaction(2000, false, timestamp + 200);
+        state.record_transaction(&mut history, 1000, true, timestamp + 100).unwrap();
+        state.record_transaction(&mut history, 2000, false, timestamp + 200).unwrap();
         
         assert_eq!(state.total_transactions, 2);
         assert_eq!(state.success_rate, 50);
         assert_eq!(state.peak_gas_used, 2000);
         assert_eq!(state.error_count, 1);
-        assert_eq!(state.gas_history.len(), 2);
+        assert_eq!(history.len(), 2);
         
         // Test gas trend
-        assert_eq!(state.get_gas_trend(), Some(1000));
+        assert_eq!(history.gas_trend(), Some(1000));
+
+        // The history takes whatever room the account leaves
+        assert_eq!(MetricsState::space(DEFAULT_GAS_HISTORY), MetricsState::SPACE);
+        let mut data = vec![0; MetricsState::space(3) + 1];
+        assert_eq!(MetricsState::init_history(&mut data).unwrap().capacity(), 3);
+        assert!(MetricsState::init_history(&mut data[..MetricsState::space(0)]).is_err());
     }
-}
\ No newline at end of file
+}